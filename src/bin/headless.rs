@@ -0,0 +1,254 @@
+/*
+    Runs a rom without any video or audio, meant for CI and scripted testing.
+
+    With --dual two GameBoys run in lockstep connected by link cable. Memory condition then has to be met on both
+    and dump has both screens next to each other.
+
+    Exit codes:
+        0 - finished: stop condition met, or all frames ran when no condition was given.
+        1 - stop condition was not met within frame limit.
+        2 - bad arguments or rom couldn't be loaded.
+        3 - framebuffer couldn't be dumped.
+*/
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::process::ExitCode;
+
+use gameooy::dual::{LEFT_SIDE, RIGHT_SIDE};
+use gameooy::printer::Printer;
+use gameooy::{DualGameBoy, GameBoy, SCREEN_WIDTH, SCREEN_HEIGHT};
+
+const USAGE: &str = "Correct usage: gameooy-headless <game rom path> [--frames <n>] [--until-mem <addr>=<value>] [--dump <output.pgm>] [--printer <output dir>] [--dual [<second game rom path>]]";
+
+const DEFAULT_FRAMES: u32 = 60 * 60;
+
+//Grey level for each colour id in dumped framebuffer.
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+struct Options {
+    rom_path: String,
+    frames: u32,
+    until_mem: Option<(u16, u8)>,
+    dump_path: Option<String>,
+    printer_dir: Option<String>,
+    dual: bool,
+    second_rom_path: Option<String>,
+}
+
+fn parse_number(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut rom_path: Option<String> = None;
+    let mut frames = DEFAULT_FRAMES;
+    let mut until_mem = None;
+    let mut dump_path = None;
+    let mut printer_dir = None;
+    let mut dual = false;
+    let mut second_rom_path: Option<String> = None;
+
+    let mut args_iter = args.iter().skip(1);
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let value = args_iter.next().ok_or("Missing value for --frames.")?;
+                frames = parse_number(value).ok_or(format!("Invalid frame count: {value}"))?;
+            }
+
+            "--until-mem" => {
+                let value = args_iter.next().ok_or("Missing value for --until-mem.")?;
+                let (addr, data) = value.split_once('=').ok_or(format!("Expected <addr>=<value>, got: {value}"))?;
+                let addr = parse_number(addr).and_then(|addr| u16::try_from(addr).ok());
+                let data = parse_number(data).and_then(|data| u8::try_from(data).ok());
+                match (addr, data) {
+                    (Some(addr), Some(data)) => until_mem = Some((addr, data)),
+                    _ => return Err(format!("Invalid memory condition: {value}")),
+                }
+            }
+
+            "--dump" => {
+                let value = args_iter.next().ok_or("Missing value for --dump.")?;
+                dump_path = Some(value.clone());
+            }
+
+            "--printer" => {
+                let value = args_iter.next().ok_or("Missing value for --printer.")?;
+                printer_dir = Some(value.clone());
+            }
+
+            "--dual" => {
+                dual = true;
+            }
+
+            _ if rom_path.is_none() && !arg.starts_with("--") => {
+                rom_path = Some(arg.clone());
+            }
+
+            _ if second_rom_path.is_none() && !arg.starts_with("--") => {
+                second_rom_path = Some(arg.clone());
+            }
+
+            _ => {
+                return Err(format!("Unknown argument: {arg}"));
+            }
+        }
+    }
+
+    let rom_path = rom_path.ok_or("Missing game rom path.")?;
+    if !Path::new(&rom_path).is_file() {
+        return Err(format!("Not a rom file: {rom_path}"));
+    }
+
+    if let Some(second_rom_path) = &second_rom_path {
+        if !dual {
+            return Err(format!("Second rom can only be used with --dual: {second_rom_path}"));
+        }
+        if !Path::new(second_rom_path).is_file() {
+            return Err(format!("Not a rom file: {second_rom_path}"));
+        }
+    }
+
+    if dual && printer_dir.is_some() {
+        return Err(String::from("--printer can't be used with --dual, link cable is already taken."));
+    }
+
+    Ok(Options {
+        rom_path,
+        frames,
+        until_mem,
+        dump_path,
+        printer_dir,
+        dual,
+        second_rom_path,
+    })
+}
+
+//Binary greyscale PGM, no extra dependencies needed and most image viewers open it. Multiple screens go side by side.
+fn dump_framebuffers(path: &str, framebuffers: &[&[u8]]) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    write!(file, "P5\n{} {}\n255\n", SCREEN_WIDTH as usize * framebuffers.len(), SCREEN_HEIGHT)?;
+
+    let mut pixels = Vec::new();
+    for row in 0..SCREEN_HEIGHT as usize {
+        for framebuffer in framebuffers {
+            let line = &framebuffer[row * SCREEN_WIDTH as usize..(row + 1) * SCREEN_WIDTH as usize];
+            pixels.extend(line.iter().map(|colour| SHADES[*colour as usize]));
+        }
+    }
+    file.write_all(&pixels)
+}
+
+enum Machine {
+    Single(Box<GameBoy>),
+    Dual(Box<DualGameBoy>),
+}
+
+impl Machine {
+    fn update(&mut self) {
+        match self {
+            Machine::Single(gameboy) => gameboy.update(),
+            Machine::Dual(dual) => dual.update(),
+        }
+    }
+
+    fn gameboys(&self) -> Vec<&GameBoy> {
+        match self {
+            Machine::Single(gameboy) => vec![gameboy],
+            Machine::Dual(dual) => vec![dual.gameboy(LEFT_SIDE), dual.gameboy(RIGHT_SIDE)],
+        }
+    }
+}
+
+fn load_gameboy(rom_path: &str) -> Option<GameBoy> {
+    let mut gameboy = GameBoy::new(rom_path.to_string());
+    if let Err(error) = gameboy.load_rom() {
+        eprintln!("{error}");
+        return None;
+    }
+    gameboy.load_boot_rom();
+
+    if let Some(header) = gameboy.cartridge_header() {
+        println!("Running {} ({}).", header.title, header.cartridge_type);
+    }
+    Some(gameboy)
+}
+
+//Printer only reports saved prints through callback, it's up to us to tell the user.
+fn new_printer(output_dir: &str) -> Printer {
+    let mut printer = Printer::new(output_dir);
+    let output_dir = output_dir.to_string();
+    printer.set_print_callback(Box::new(move |result| match result {
+        Ok(path) => println!("Printed {}", path.display()),
+        Err(error) => eprintln!("Printer couldn't save image to {output_dir}: {error}"),
+    }));
+    printer
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}");
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let Some(mut gameboy) = load_gameboy(&options.rom_path) else {
+        return ExitCode::from(2);
+    };
+    if let Some(printer_dir) = &options.printer_dir {
+        gameboy.connect_link_peer(Box::new(new_printer(printer_dir)));
+    }
+
+    let mut machine = if options.dual {
+        let Some(second) = load_gameboy(options.second_rom_path.as_deref().unwrap_or(&options.rom_path)) else {
+            return ExitCode::from(2);
+        };
+        Machine::Dual(Box::new(DualGameBoy::new(gameboy, second)))
+    }
+    else {
+        Machine::Single(Box::new(gameboy))
+    };
+
+    let mut condition_met = false;
+    let mut frames_ran = 0;
+
+    while frames_ran < options.frames {
+        machine.update();
+        frames_ran += 1;
+
+        if let Some((addr, data)) = options.until_mem {
+            if machine.gameboys().iter().all(|gameboy| gameboy.read_memory(addr) == data) {
+                condition_met = true;
+                break;
+            }
+        }
+    }
+
+    if let Some(dump_path) = &options.dump_path {
+        let framebuffers: Vec<&[u8]> = machine.gameboys().iter().map(|gameboy| &gameboy.ppu.framebuffer()[..]).collect();
+        if let Err(error) = dump_framebuffers(dump_path, &framebuffers) {
+            eprintln!("Failed to dump framebuffer to {dump_path}: {error}");
+            return ExitCode::from(3);
+        }
+    }
+
+    println!("Ran {frames_ran} frames.");
+
+    if options.until_mem.is_some() && !condition_met {
+        eprintln!("Stop condition not met within {} frames.", options.frames);
+        return ExitCode::from(1);
+    }
+
+    ExitCode::SUCCESS
+}
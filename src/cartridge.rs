@@ -0,0 +1,369 @@
+/*
+    Cartridge header, 0x0100 - 0x014F of every rom.
+    https://gbdev.io/pandocs/The_Cartridge_Header.html
+*/
+
+use crate::boot_rom::BOOT_ROM;
+use crate::error::LoadError;
+
+pub const HEADER_START: usize = 0x0100;
+pub const HEADER_END: usize = 0x0150;
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+
+//Where boot rom keeps its copy of nintendo logo.
+const NINTENDO_LOGO_START: usize = 0xA8;
+const NINTENDO_LOGO_LENGTH: usize = 48;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapperKind {
+    RomOnly,
+    MBC1,
+    MBC2,
+    MMM01,
+    MBC3,
+    MBC5,
+    MBC6,
+    MBC7,
+    PocketCamera,
+    TAMA5,
+    HuC3,
+    HuC1,
+    Unknown,
+}
+
+/*
+    Decoded cartridge type byte (0x0147). Mapper plus whatever extra hardware is on the board.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mapper: MapperKind,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+    pub sensor: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> CartridgeType {
+        let mut cartridge_type = CartridgeType {
+            code,
+            mapper: MapperKind::Unknown,
+            ram: false,
+            battery: false,
+            timer: false,
+            rumble: false,
+            sensor: false,
+        };
+
+        match code {
+            0x00 => cartridge_type.mapper = MapperKind::RomOnly,
+            0x01 => cartridge_type.mapper = MapperKind::MBC1,
+            0x02 => {
+                cartridge_type.mapper = MapperKind::MBC1;
+                cartridge_type.ram = true;
+            }
+            0x03 => {
+                cartridge_type.mapper = MapperKind::MBC1;
+                cartridge_type.ram = true;
+                cartridge_type.battery = true;
+            }
+            0x05 => cartridge_type.mapper = MapperKind::MBC2,
+            0x06 => {
+                cartridge_type.mapper = MapperKind::MBC2;
+                cartridge_type.battery = true;
+            }
+            0x08 => {
+                cartridge_type.mapper = MapperKind::RomOnly;
+                cartridge_type.ram = true;
+            }
+            0x09 => {
+                cartridge_type.mapper = MapperKind::RomOnly;
+                cartridge_type.ram = true;
+                cartridge_type.battery = true;
+            }
+            0x0B => cartridge_type.mapper = MapperKind::MMM01,
+            0x0C => {
+                cartridge_type.mapper = MapperKind::MMM01;
+                cartridge_type.ram = true;
+            }
+            0x0D => {
+                cartridge_type.mapper = MapperKind::MMM01;
+                cartridge_type.ram = true;
+                cartridge_type.battery = true;
+            }
+            0x0F => {
+                cartridge_type.mapper = MapperKind::MBC3;
+                cartridge_type.timer = true;
+                cartridge_type.battery = true;
+            }
+            0x10 => {
+                cartridge_type.mapper = MapperKind::MBC3;
+                cartridge_type.timer = true;
+                cartridge_type.ram = true;
+                cartridge_type.battery = true;
+            }
+            0x11 => cartridge_type.mapper = MapperKind::MBC3,
+            0x12 => {
+                cartridge_type.mapper = MapperKind::MBC3;
+                cartridge_type.ram = true;
+            }
+            0x13 => {
+                cartridge_type.mapper = MapperKind::MBC3;
+                cartridge_type.ram = true;
+                cartridge_type.battery = true;
+            }
+            0x19 => cartridge_type.mapper = MapperKind::MBC5,
+            0x1A => {
+                cartridge_type.mapper = MapperKind::MBC5;
+                cartridge_type.ram = true;
+            }
+            0x1B => {
+                cartridge_type.mapper = MapperKind::MBC5;
+                cartridge_type.ram = true;
+                cartridge_type.battery = true;
+            }
+            0x1C => {
+                cartridge_type.mapper = MapperKind::MBC5;
+                cartridge_type.rumble = true;
+            }
+            0x1D => {
+                cartridge_type.mapper = MapperKind::MBC5;
+                cartridge_type.rumble = true;
+                cartridge_type.ram = true;
+            }
+            0x1E => {
+                cartridge_type.mapper = MapperKind::MBC5;
+                cartridge_type.rumble = true;
+                cartridge_type.ram = true;
+                cartridge_type.battery = true;
+            }
+            0x20 => cartridge_type.mapper = MapperKind::MBC6,
+            0x22 => {
+                cartridge_type.mapper = MapperKind::MBC7;
+                cartridge_type.sensor = true;
+                cartridge_type.rumble = true;
+                cartridge_type.ram = true;
+                cartridge_type.battery = true;
+            }
+            0xFC => cartridge_type.mapper = MapperKind::PocketCamera,
+            0xFD => cartridge_type.mapper = MapperKind::TAMA5,
+            0xFE => cartridge_type.mapper = MapperKind::HuC3,
+            0xFF => {
+                cartridge_type.mapper = MapperKind::HuC1;
+                cartridge_type.ram = true;
+                cartridge_type.battery = true;
+            }
+            _ => {}
+        }
+
+        cartridge_type
+    }
+}
+
+impl std::fmt::Display for CartridgeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mapper {
+            MapperKind::RomOnly => write!(f, "ROM")?,
+            MapperKind::Unknown => return write!(f, "UNKNOWN ({:#04x})", self.code),
+            mapper => write!(f, "{mapper:?}")?,
+        }
+
+        if self.timer {
+            write!(f, "+TIMER")?;
+        }
+        if self.rumble {
+            write!(f, "+RUMBLE")?;
+        }
+        if self.sensor {
+            write!(f, "+SENSOR")?;
+        }
+        if self.ram {
+            write!(f, "+RAM")?;
+        }
+        if self.battery {
+            write!(f, "+BATTERY")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgbSupport {
+    //Plain DMG game.
+    None,
+    //Works on DMG, uses CGB features when available. 0x80.
+    Enhanced,
+    //Refuses to run on DMG. 0xC0.
+    Only,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    //0x0100 - 0x0103, usually NOP; JP 0x0150.
+    pub entry_point: [u8; 4],
+    //0x0104 - 0x0133, boot rom refuses to start if it doesn't match.
+    pub logo: [u8; 48],
+
+    //0x0134 - 0x0143, shorter on newer carts which use last bytes for manufacturer code and CGB flag.
+    pub title: String,
+    //0x013F - 0x0142, only on newer carts.
+    pub manufacturer_code: Option<String>,
+    //0x0143
+    pub cgb_flag: u8,
+    //0x0144 - 0x0145, used when old licensee code is 0x33.
+    pub new_licensee_code: String,
+    //0x0146
+    pub sgb_flag: u8,
+    //0x0147
+    pub cartridge_type: CartridgeType,
+    //0x0148
+    pub rom_size_code: u8,
+    //0x0149
+    pub ram_size_code: u8,
+    //0x014A, 0x00 = Japan, 0x01 = overseas.
+    pub destination_code: u8,
+    //0x014B
+    pub old_licensee_code: u8,
+    //0x014C
+    pub version: u8,
+    //0x014D
+    pub header_checksum: u8,
+    //0x014E - 0x014F, big endian.
+    pub global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    pub fn parse(rom: &[u8]) -> Result<CartridgeHeader, LoadError> {
+        if rom.len() < HEADER_END {
+            return Err(LoadError::TruncatedHeader(rom.len()));
+        }
+
+        //Unknown size codes are kept as they are, it's up to whoever uses the header to reject them.
+        let rom_size_code = rom[0x0148];
+        let ram_size_code = rom[0x0149];
+
+        let cgb_flag = rom[0x0143];
+        let manufacturer_bytes = &rom[0x013F..0x0143];
+
+        /*
+            There is no flag telling if manufacturer code is there. On carts that have it it's 4 uppercase letters/digits
+            and title is cut to 11 bytes. Older carts can have title running all the way through.
+        */
+        let has_manufacturer_code = manufacturer_bytes.iter().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+            && (cgb_flag & 0x80) != 0;
+
+        let title_end = if has_manufacturer_code {
+            0x013F
+        }
+        else if (cgb_flag & 0x80) != 0 {
+            0x0143
+        }
+        else {
+            0x0144
+        };
+
+        let mut entry_point = [0; 4];
+        entry_point.copy_from_slice(&rom[0x0100..0x0104]);
+        let mut logo = [0; 48];
+        logo.copy_from_slice(&rom[0x0104..0x0134]);
+
+        Ok(CartridgeHeader {
+            entry_point,
+            logo,
+
+            title: header_string(&rom[0x0134..title_end]),
+            manufacturer_code: has_manufacturer_code.then(|| header_string(manufacturer_bytes)),
+            cgb_flag,
+            new_licensee_code: header_string(&rom[0x0144..0x0146]),
+            sgb_flag: rom[0x0146],
+            cartridge_type: CartridgeType::from_code(rom[0x0147]),
+            rom_size_code,
+            ram_size_code,
+            destination_code: rom[0x014A],
+            old_licensee_code: rom[0x014B],
+            version: rom[0x014C],
+            header_checksum: rom[0x014D],
+            global_checksum: ((rom[0x014E] as u16) << 8) | rom[0x014F] as u16,
+        })
+    }
+
+    pub fn cgb_support(&self) -> CgbSupport {
+        match self.cgb_flag {
+            0xC0 => CgbSupport::Only,
+            0x80 => CgbSupport::Enhanced,
+            _ => CgbSupport::None,
+        }
+    }
+
+    pub fn supports_sgb(&self) -> bool {
+        self.sgb_flag == 0x03
+    }
+
+    //32KiB * 2^code. 0x52 - 0x54 only show up in a few unofficial headers. None for unknown codes.
+    pub fn rom_size(&self) -> Option<usize> {
+        match self.rom_size_code {
+            0x52 => Some(72 * ROM_BANK_SIZE),
+            0x53 => Some(80 * ROM_BANK_SIZE),
+            0x54 => Some(96 * ROM_BANK_SIZE),
+            code @ 0x00..=0x08 => Some(0x8000 << code),
+            _ => None,
+        }
+    }
+
+    pub fn rom_banks(&self) -> Option<usize> {
+        self.rom_size().map(|size| size / ROM_BANK_SIZE)
+    }
+
+    pub fn ram_size(&self) -> Option<usize> {
+        match self.ram_size_code {
+            0x00 => Some(0),
+            0x01 => Some(0x800),
+            0x02 => Some(0x2000),
+            0x03 => Some(0x8000),
+            0x04 => Some(0x20000),
+            0x05 => Some(0x10000),
+            _ => None,
+        }
+    }
+
+    //Partial banks (2KiB carts) still take whole bank window.
+    pub fn ram_banks(&self) -> Option<usize> {
+        self.ram_size().map(|size| size.div_ceil(RAM_BANK_SIZE))
+    }
+
+    //Same logo boot rom compares against, it locks up if they don't match.
+    pub fn logo_valid(&self) -> bool {
+        self.logo == nintendo_logo()
+    }
+}
+
+//Logo every cartridge has to carry at 0x0104 - 0x0133.
+pub fn nintendo_logo() -> &'static [u8] {
+    &BOOT_ROM[NINTENDO_LOGO_START..NINTENDO_LOGO_START + NINTENDO_LOGO_LENGTH]
+}
+
+/*
+    Checked by boot rom, game won't start if it's wrong.
+    x = 0; for each byte in 0x0134..=0x014C: x = x - byte - 1
+*/
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[0x0134..=0x014C].iter().fold(0_u8, |checksum, byte| checksum.wrapping_sub(*byte).wrapping_sub(1))
+}
+
+//Sum of every rom byte except checksum itself. Real hardware never checks it.
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(index, _)| *index != 0x014E && *index != 0x014F)
+        .fold(0_u16, |checksum, (_, byte)| checksum.wrapping_add(*byte as u16))
+}
+
+//Header strings are padded with zeros, some games also pad with spaces.
+fn header_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim_end().to_string()
+}
@@ -20,14 +20,43 @@ pub struct Cpu {
     //interrupt flag 
     pub IME_enabled: bool,
 
+    //EI enables interrupts only after instruction following it is done.
+    IME_scheduled: bool,
+
     pub is_halted: bool,
 
+    pub is_stopped: bool,
+
+    /*
+        HALT executed with IME off while interrupt is already pending doesn't halt,
+        instead cpu fails to increment pc after reading next opcode, so that byte gets read twice.
+    */
+    halt_bug: bool,
+
     pub sp: u16,
     pub pc: u16,
 
     databus: Rc<RefCell<DataBus>>,
 }
 
+/*
+    Read-only copy of cpu registers, for debuggers and test harnesses.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    pub A: u8,
+    pub B: u8,
+    pub C: u8,
+    pub D: u8,
+    pub E: u8,
+    pub H: u8,
+    pub L: u8,
+    pub F: u8,
+
+    pub sp: u16,
+    pub pc: u16,
+}
+
 impl std::fmt::Display for Cpu {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "
@@ -62,8 +91,14 @@ impl Cpu {
 
             IME_enabled: false,
 
+            IME_scheduled: false,
+
             is_halted: false,
 
+            is_stopped: false,
+
+            halt_bug: false,
+
             sp: 0x0000,
             pc: 0x0000,
 
@@ -71,6 +106,22 @@ impl Cpu {
         }
     }
 
+    pub fn registers(&self) -> Registers {
+        Registers {
+            A: self.A,
+            B: self.B,
+            C: self.C,
+            D: self.D,
+            E: self.E,
+            H: self.H,
+            L: self.L,
+            F: self.F,
+
+            sp: self.sp,
+            pc: self.pc,
+        }
+    }
+
     fn flip_c(&mut self){
         self.set_n_to(false);
         self.set_h_to(false);
@@ -698,11 +749,23 @@ impl Cpu {
         result
     }
 
+    //Any interrupt both enabled in IE and requested in IF, regardless of IME.
+    pub fn interrupt_pending(&self) -> bool {
+        self.databus.borrow().interrupts.has_pending()
+    }
+
     pub fn exec_instruction(&mut self, instruction_byte: u8) -> u8 {
         let instruction = INSTRUCTIONS_MAP.get(&instruction_byte).unwrap();
+
+        //Opcode was already fetched, step pc back so operands and next opcode start from the same byte again.
+        if self.halt_bug {
+            self.halt_bug = false;
+            self.pc = self.pc.wrapping_sub(1);
+        }
         let mut jumped: bool = false;
         let mut condition_met: bool = false;
         let mut total_cycles: u8 = 0;
+        let IME_was_scheduled = self.IME_scheduled;
 
         if crate::DEBUG {
             println!("
@@ -1083,25 +1146,25 @@ impl Cpu {
             }
 
             Mnemonic::DI => {
-                self.pc += 1;
-                jumped = true;
-                let next_instruction = self.databus.borrow().read_memory(self.pc);
-                self.exec_instruction(next_instruction);
                 self.IME_enabled = false;
+                self.IME_scheduled = false;
             }
 
             Mnemonic::EI => {
-                self.pc += 1;
-                jumped = true;
-                let next_instruction = self.databus.borrow().read_memory(self.pc);
-                self.exec_instruction(next_instruction);
-                self.IME_enabled = true;
+                self.IME_scheduled = true;
             }
 
             Mnemonic::HALT => {
-                if self.IME_enabled {
+                if self.IME_enabled || !self.interrupt_pending() {
                     self.is_halted = true;
                 }
+                else if self.IME_scheduled {
+                    //EI; HALT with interrupt pending. Interrupt gets serviced right away and returns back to this HALT.
+                    jumped = true;
+                }
+                else {
+                    self.halt_bug = true;
+                }
             }
 
             Mnemonic::INC => {
@@ -1884,17 +1947,8 @@ impl Cpu {
             Mnemonic::RET => {
                 let mut new_pc: u16 = 0x0000;
 
-                if instruction.operands.is_none() {
-                    let (msb, lsb) = self.POP();
-
-                    new_pc |= msb as u16;
-                    new_pc <<= 8;
-                    new_pc |= lsb as u16;
-                    self.pc = new_pc; 
-                    jumped = true;
-                }
-                else {
-                    match instruction.operands.as_ref().unwrap() {
+                if let Some(operands) = instruction.operands.as_ref() {
+                    match operands {
 
                         [Operand::NZ, Operand::none] => {
                             if !self.get_z() {
@@ -1953,6 +2007,15 @@ impl Cpu {
                         }
                     }
                 }
+                else {
+                    let (msb, lsb) = self.POP();
+
+                    new_pc |= msb as u16;
+                    new_pc <<= 8;
+                    new_pc |= lsb as u16;
+                    self.pc = new_pc; 
+                    jumped = true;
+                }
             }
 
             Mnemonic::RETI => {
@@ -2117,6 +2180,8 @@ impl Cpu {
             }
 
             Mnemonic::STOP => {
+                //Second byte is skipped by instruction length, GameBoy takes care of the rest.
+                self.is_stopped = true;
             }
 
             Mnemonic::SUB => {
@@ -2228,6 +2293,12 @@ impl Cpu {
             self.pc = self.pc.wrapping_add(instruction.length as u16);
         }
 
+        //Instruction after EI is done, unless it was DI which cancels it.
+        if IME_was_scheduled && self.IME_scheduled {
+            self.IME_scheduled = false;
+            self.IME_enabled = true;
+        }
+
         match instruction.cycles {
             cycles_length::non_conditional(cycles) => {
                 total_cycles += cycles;
@@ -1,87 +1,76 @@
+use crate::boot_rom::BOOT_ROM;
+use crate::mapper::{Mapper, RomOnly};
+use crate::interrupts::{InterruptController, IE_ADDRESS, IF_ADDRESS};
+use crate::serial::{Serial, SB_ADDRESS, SC_ADDRESS};
+use crate::timer::{Timer, DIV_ADDRESS, TIMA_ADDRESS, TMA_ADDRESS, TAC_ADDRESS};
+
+const KEY1_ADDRESS: u16 = 0xFF4D;
+
 pub struct DataBus{
 
     pub memory: [u8; 65536],
-    pub cartridge_rom: Vec<u8>,
-    pub cartridge_ram: Vec<u8>,
 
-    pub ROM_banks_count: u32,
-    current_ROM_bank: u8,
+    //Cartridge rom and RAM, 0x0000 - 0x7FFF and 0xA000 - 0xBFFF.
+    pub mapper: Box<dyn Mapper>,
+    //Boot rom covers 0x0000 - 0x00FF until game starts.
+    pub boot_rom_mapped: bool,
 
     pub joypad_state: u8,
 
-    pub RAM_banks_count: u8,
-    current_RAM_bank: u8,
-    RAM_banks_enabled: bool,
+    pub serial: Serial,
+
+    pub interrupts: InterruptController,
 
-    banking_mode: u8,
+    pub timer: Timer,
 
-    pub MBC1: bool,
-    pub MBC2: bool,
+    //CGB only. KEY1 (0xFF4D) bit0 arms speed switch, STOP then performs it.
+    pub cgb_mode: bool,
+    pub speed_switch_armed: bool,
+    pub double_speed: bool,
+}
+
+impl Default for DataBus {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DataBus {
     pub fn new() -> DataBus {
         let memory = [0; 65536];
-        let cartridge_rom = Vec::new();
-        let cartridge_ram = Vec::new();
 
         DataBus {
             memory,
-            cartridge_rom,
-            cartridge_ram,
 
-            ROM_banks_count: 2,
-            current_ROM_bank: 1,
+            mapper: Box::new(RomOnly::new(Vec::new(), 0, false)),
+            boot_rom_mapped: false,
 
             joypad_state: 0xFF,
-            
-            RAM_banks_count: 0,
-            current_RAM_bank: 0,
-            RAM_banks_enabled: false,
 
-            banking_mode: 1,
+            serial: Serial::new(),
 
-            MBC1: false,
-            MBC2: false,
-        }
-    }
+            interrupts: InterruptController::new(),
 
-    pub fn load_boot_rom(&mut self, index: usize, data: u8) {
-        self.memory[index] = data;
-    }
+            timer: Timer::new(),
 
-    pub fn load_rom(&mut self, index: usize, data: u8) {
-        if index >= 0x8000 {
-            self.cartridge_rom[index - 0x8000] = data;
-            return;
+            cgb_mode: false,
+            speed_switch_armed: false,
+            double_speed: false,
         }
-        self.memory[index] = data;
-    }
-
-    pub fn increment_div_timer(&mut self) {
-        self.memory[0xFF04] = self.memory[0xFF04].wrapping_add(1);
     }
 
     pub fn read_memory(&self, addr: u16) -> u8{
         match addr {
-            0x4000..=0x7FFF => {
-                //rom banks
-                if self.current_ROM_bank == 1 {
-                    return self.memory[addr as usize]
-                }
-                let new_address: u32 = (addr as u32 - 0x4000) + ((self.current_ROM_bank - 2) as u32 * 0x4000);
-                self.cartridge_rom[new_address as usize]
-                }
+            0x0000..=0x00FF if self.boot_rom_mapped => {
+                BOOT_ROM[addr as usize]
+            }
+
+            0x0000..=0x7FFF => {
+                self.mapper.read_rom(addr)
+            }
 
             0xA000..=0xBFFF => {
-                //ram banks
-                if self.RAM_banks_enabled {
-                    let new_address = (addr - 0xA000) + (self.current_RAM_bank as u16 * 0x2000);
-                    self.cartridge_ram[new_address as usize]
-                }
-                else {
-                    0xFF
-                }
+                self.mapper.read_ram(addr)
             }
 
             0xFF00 => {
@@ -105,72 +94,61 @@ impl DataBus {
                 }
             }
 
-            _ => {
-                self.memory[addr as usize]
+            SB_ADDRESS => {
+                self.serial.read_SB()
             }
-        }
-    }
 
-    pub fn write_memory(&mut self, data: u8, addr: u16) {
-        match addr {
-            0x0000..=0x1FFF => {
-                //eanbling or disabling ram banking
-                if (self.MBC1 || self.MBC2) && self.RAM_banks_count > 0 {
-                    if (self.MBC2) && ((addr & 0x100) > 0) {
-                        return;
-                    }
-                    self.RAM_banks_enabled = data & 0x0F == 0x0A;
-                }
+            SC_ADDRESS => {
+                self.serial.read_SC()
             }
 
-            0x2000..=0x3FFF => {
-                //switch rom bank
-                if self.MBC1 {
-                    let lower5 = data & 0x1F;
-                    self.current_ROM_bank &= 0xE0;
-                    self.current_ROM_bank |= lower5;
+            DIV_ADDRESS => {
+                self.timer.read_DIV()
+            }
 
-                    if self.current_ROM_bank == 0x00 {
-                        self.current_ROM_bank = 0x01;
-                    }
+            TIMA_ADDRESS => {
+                self.timer.read_TIMA()
+            }
+
+            TMA_ADDRESS => {
+                self.timer.read_TMA()
+            }
+
+            TAC_ADDRESS => {
+                self.timer.read_TAC()
+            }
+
+            IF_ADDRESS => {
+                self.interrupts.read_IF()
+            }
+
+            IE_ADDRESS => {
+                self.interrupts.read_IE()
+            }
+
+            KEY1_ADDRESS => {
+                if self.cgb_mode {
+                    ((self.double_speed as u8) << 7) | 0x7E | self.speed_switch_armed as u8
                 }
-                if self.MBC2 {
-                    self.current_ROM_bank = data & 0x0F;
-                    if self.current_ROM_bank == 0 {
-                        self.current_ROM_bank = 1;
-                    }
+                else {
+                    0xFF
                 }
             }
 
-            0x4000..= 0x5FFF => {
-                //ram bank number
-                if self.MBC1 {
-                    if self.banking_mode == 0 && self.RAM_banks_count > 0 {
-                        self.current_RAM_bank = data & 0x3;
-                    }
-                    if self.banking_mode == 1 && self.ROM_banks_count > 32 {
-                        self.current_ROM_bank &= 0x1F;
-                        self.current_ROM_bank |= data & 0xE0;
-                        if self.current_ROM_bank == 0 {
-                            self.current_ROM_bank = 1;
-                        }
-                    }
-                }
+            _ => {
+                self.memory[addr as usize]
             }
+        }
+    }
 
-            0x6000..=0x7FFF => {
-                //banking mode select
-                if self.MBC1 {
-                    self.banking_mode = data & 0x01;
-                }
+    pub fn write_memory(&mut self, data: u8, addr: u16) {
+        match addr {
+            0x0000..=0x7FFF => {
+                self.mapper.write_control(addr, data);
             }
 
             0xA000..=0xBFFF => {
-                //writing to RAM banks
-                if self.RAM_banks_enabled {
-                    let new_address = (addr - 0xA000) + (self.current_RAM_bank as u16 * 0x2000);
-                    self.cartridge_ram[new_address as usize] = data;
-                }
+                self.mapper.write_ram(addr, data);
             }
 
             0xE000..=0xFDFF => {
@@ -187,9 +165,43 @@ impl DataBus {
                 self.memory[0xFF00] |= input_mode << 4;
             }
 
+            SB_ADDRESS => {
+                self.serial.write_SB(data);
+            }
+
+            SC_ADDRESS => {
+                self.serial.write_SC(data);
+            }
+
+            IF_ADDRESS => {
+                self.interrupts.write_IF(data);
+            }
+
+            IE_ADDRESS => {
+                self.interrupts.write_IE(data);
+            }
+
+            KEY1_ADDRESS => {
+                if self.cgb_mode {
+                    self.speed_switch_armed = (data & 0x01) != 0;
+                }
+            }
+
             //writes to div timer reset it
-            0xFF04 => {
-                self.memory[addr as usize] = 0x00;
+            DIV_ADDRESS => {
+                self.timer.write_DIV();
+            }
+
+            TIMA_ADDRESS => {
+                self.timer.write_TIMA(data);
+            }
+
+            TMA_ADDRESS => {
+                self.timer.write_TMA(data);
+            }
+
+            TAC_ADDRESS => {
+                self.timer.write_TAC(data);
             }
 
             0xFF40 => {
@@ -0,0 +1,91 @@
+/*
+    Two GameBoys in one process, connected with virtual link cable.
+
+    Both run on same thread and are stepped one instruction at a time, always advancing whichever one is behind,
+    so they never drift apart by more than single instruction. Same roms and same input always give same result,
+    which makes multiplayer serial protocols testable.
+*/
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::gameboy::GameBoy;
+use crate::serial::{LinkPeer, SlaveState};
+
+pub const LEFT_SIDE: usize = 0;
+pub const RIGHT_SIDE: usize = 1;
+
+/*
+    One plug of the cable. Master clocks straight into other side's slave state, nothing to wait for.
+*/
+struct CableEnd {
+    side: usize,
+    slaves: Rc<RefCell<[SlaveState; 2]>>,
+}
+
+impl LinkPeer for CableEnd {
+    fn exchange(&mut self, outgoing: u8) -> u8 {
+        self.slaves.borrow_mut()[1 - self.side].clock(outgoing)
+    }
+
+    fn poll_external(&mut self, outgoing: u8) -> Option<u8> {
+        self.slaves.borrow_mut()[self.side].poll(outgoing)
+    }
+
+    fn ignore_external(&mut self) {
+        self.slaves.borrow_mut()[self.side].ignore();
+    }
+}
+
+pub struct DualGameBoy {
+    gameboys: [GameBoy; 2],
+
+    //Cycles left side is ahead of right side.
+    skew: i64,
+}
+
+impl DualGameBoy {
+    //Both need roms loaded already, cable gets plugged into both.
+    pub fn new(mut left: GameBoy, mut right: GameBoy) -> DualGameBoy {
+        let slaves = Rc::new(RefCell::new([SlaveState::default(), SlaveState::default()]));
+        left.connect_link_peer(Box::new(CableEnd { side: LEFT_SIDE, slaves: Rc::clone(&slaves) }));
+        right.connect_link_peer(Box::new(CableEnd { side: RIGHT_SIDE, slaves }));
+
+        DualGameBoy {
+            gameboys: [left, right],
+            skew: 0,
+        }
+    }
+
+    //Runs both for one frame.
+    pub fn update(&mut self) {
+        loop {
+            let [left, right] = &mut self.gameboys;
+            let left_done = left.frame_complete();
+            let right_done = right.frame_complete();
+
+            if left_done && right_done {
+                break;
+            }
+
+            if !left_done && (right_done || self.skew <= 0) {
+                self.skew += left.step() as i64;
+            }
+            else {
+                self.skew -= right.step() as i64;
+            }
+        }
+
+        for gameboy in &mut self.gameboys {
+            gameboy.end_frame();
+        }
+    }
+
+    pub fn gameboy(&self, side: usize) -> &GameBoy {
+        &self.gameboys[side]
+    }
+
+    pub fn gameboy_mut(&mut self, side: usize) -> &mut GameBoy {
+        &mut self.gameboys[side]
+    }
+}
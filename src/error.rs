@@ -0,0 +1,64 @@
+use std::fmt;
+
+/*
+    Everything that can go wrong while loading game rom.
+*/
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    //Rom too short to contain cartridge header (0x0100 - 0x014F).
+    TruncatedHeader(usize),
+    //Cartridge type byte at 0x0147.
+    UnsupportedMapper(u8),
+    //ROM size byte at 0x0148.
+    UnsupportedRomSize(u8),
+    //RAM size byte at 0x0149.
+    UnsupportedRamSize(u8),
+    //Rom file is bigger than its header says.
+    RomSizeMismatch { header: usize, file: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => {
+                write!(f, "Can't read rom file: {error}")
+            }
+
+            LoadError::TruncatedHeader(len) => {
+                write!(f, "Rom is too short to contain cartridge header ({len} bytes).")
+            }
+
+            LoadError::UnsupportedMapper(cartridge_type) => {
+                write!(f, "Not supported cartridge type: {cartridge_type:#04x}.")
+            }
+
+            LoadError::UnsupportedRomSize(rom_size) => {
+                write!(f, "Not supported rom size: {rom_size:#04x}.")
+            }
+
+            LoadError::UnsupportedRamSize(ram_size) => {
+                write!(f, "Not supported ram size: {ram_size:#04x}.")
+            }
+
+            LoadError::RomSizeMismatch { header, file } => {
+                write!(f, "Rom file is {file} bytes but its header says {header} bytes.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LoadError {
+    fn from(error: std::io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
@@ -1,32 +1,34 @@
 use std::cell::RefCell;
-use std::path::Path;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-
 use crate::cpu::Cpu;
 use crate::ppu::Ppu;
 use crate::databus::DataBus;
-
-const CPU_FREQUENCY: u32 = 4_213_440;
-const FPS: u32 = 60;
-
-const TIMA_ADDRESS: u16 = 0xFF05;
-const TMA_ADDRESS:  u16 = 0xFF06;
-const TAC_ADDRESS:  u16 = 0xFF07;
-const IE_ADDRESS:   u16 = 0xFFFF;
-const IF_ADDRESS:   u16 = 0xFF0F;
-
-
-pub struct GameBoy<'a>{
+use crate::error::LoadError;
+use crate::cartridge::{CartridgeHeader, CgbSupport};
+use crate::mapper::{self, RtcClock};
+use crate::serial::LinkPeer;
+use crate::interrupts::{Interrupt, DISPATCH_CYCLES};
+
+pub const CPU_FREQUENCY: u32 = 4_194_304;
+//154 scanlines * 456 cycles. Gives ~59.73 frames per second.
+pub const CYCLES_PER_FRAME: u32 = 70224;
+//Leaving HALT takes one M-cycle before next instruction or interrupt dispatch starts.
+const HALT_EXIT_CYCLES: u32 = 4;
+
+pub struct GameBoy {
     game_rom_path: String,
+    cartridge_header: Option<CartridgeHeader>,
 
     cycles_this_frame: u32,
+    rtc_clock: RtcClock,
 
-    divide_counter: u32,
-    tima_counter: u32,
-    clock_frequency: u32,
+    //Last motor state reported to frontend.
+    rumble: bool,
+    rumble_callback: Option<Box<dyn FnMut(bool)>>,
 
     /*
         1 = NOT pressed
@@ -44,255 +46,300 @@ pub struct GameBoy<'a>{
     joypad_state: u8,
 
     cpu: Cpu,
-    pub ppu: Ppu<'a>,
+    pub ppu: Ppu,
     databus: Rc<RefCell<DataBus>>,
 }
 
-impl<'a> GameBoy<'a> {
-    pub fn new(canvas: &mut Canvas<Window>, game_rom_path: String) -> GameBoy {
+impl GameBoy {
+    pub fn new(game_rom_path: String) -> GameBoy {
         let databus: Rc<RefCell<DataBus>> = Rc::new(RefCell::new(DataBus::new()));
         GameBoy {
             game_rom_path,
+            cartridge_header: None,
 
             cycles_this_frame: 0,
+            rtc_clock: RtcClock::Emulated,
 
-            divide_counter: 0,
-            tima_counter: 0,
-            clock_frequency: 256,
+            rumble: false,
+            rumble_callback: None,
 
             joypad_state: 0xFF,
 
             databus: Rc::clone(&databus),
             cpu: Cpu::new(Rc::clone(&databus)),
-            ppu: Ppu::new(Rc::clone(&databus), canvas),
+            ppu: Ppu::new(Rc::clone(&databus)),
         }
     }
 
     pub fn update(&mut self) {
-        let MAXCYCLES: u32 = CPU_FREQUENCY / FPS; 
-        // 70224 cpu cycles per frame.
-        while self.cycles_this_frame < MAXCYCLES {
-            let cycles = self.exec_next_instruction() as u32;
-            self.cycles_this_frame += cycles;
-            self.update_timers(cycles);
-            self.ppu.update_graphics(cycles);
-            self.do_interrupts();
-            /*
-               0x0000 - 0x0099 initially has boot rom.
-               0x0100 - 0x0103 is entry point of game rom. Once we get there we need to overwrite boot rom with game data.
-               */
-            if self.cpu.pc == 0x0100 {
-                self.overwrite_boot_rom();
-            }
+        while !self.frame_complete() {
+            self.step();
         }
-        self.ppu.present();
-        self.cycles_this_frame = 0;
+        self.end_frame();
     }
 
-    fn request_interupt(&mut self, interupt_id: u8) {
-        let IF = self.databus.borrow().read_memory(0xFF0F);
-        let updated_IF = IF | (1 << interupt_id);
-        self.databus.borrow_mut().write_memory(updated_IF, 0xFF0F);
+    fn frame_cycles(&self) -> u32 {
+        if self.databus.borrow().double_speed {
+            CYCLES_PER_FRAME * 2
+        }
+        else {
+            CYCLES_PER_FRAME
+        }
     }
 
-    fn update_div_timer(&mut self, cycles: u32) {
-        self.divide_counter += cycles;
-        if self.divide_counter >= 255 {
-            self.databus.borrow_mut().increment_div_timer();
-            self.divide_counter = 0;
-        }
+    //For driving step() by hand, e.g. running multiple GameBoys in lockstep.
+    pub fn frame_complete(&self) -> bool {
+        self.cycles_this_frame >= self.frame_cycles()
     }
 
-    fn update_clock_frequency(&mut self) {
-        let TAC = self.databus.borrow().read_memory(TAC_ADDRESS);
-        let CLOCK_SPEED = TAC & 0x03;
+    pub fn end_frame(&mut self) {
+        //Last instruction usually overshoots frame, carry that into next one.
+        self.cycles_this_frame -= self.frame_cycles().min(self.cycles_this_frame);
+    }
 
-        match CLOCK_SPEED {
-            0 => {
-                self.clock_frequency = 256;
+    //Execute single instruction and let rest of the hardware catch up. Returns cycles taken.
+    pub fn step(&mut self) -> u32 {
+        //Stopped cpu doesn't execute anything and whole system is frozen until button gets pressed.
+        if self.cpu.is_stopped {
+            self.cycles_this_frame += 4;
+            if self.joypad_line_low() {
+                self.cpu.is_stopped = false;
             }
+            return 4;
+        }
 
-            1 => {
-                self.clock_frequency = 4;
-            }
+        //Halted cpu doesn't execute anything, rest of the hardware keeps running.
+        let mut cycles = if self.cpu.is_halted {
+            4
+        }
+        else {
+            self.exec_next_instruction() as u32
+        };
+        self.update_hardware(cycles);
 
-            2 => {
-                self.clock_frequency = 16;
-            }
-            
-            3 => {
-                self.clock_frequency = 64;
-            }
+        if self.cpu.is_stopped {
+            self.enter_stop_mode();
+        }
 
-            _ => {
-                panic!("UNREACHABLE. Non existing clock speed.");
-            }
+        //Any pending interrupt wakes cpu up, even with IME off. It just won't be serviced then.
+        if self.cpu.is_halted && self.cpu.interrupt_pending() {
+            self.cpu.is_halted = false;
+            self.update_hardware(HALT_EXIT_CYCLES);
+            cycles += HALT_EXIT_CYCLES;
+        }
+
+        let interrupt_cycles = self.do_interrupts();
+        if interrupt_cycles > 0 {
+            self.update_hardware(interrupt_cycles);
+            cycles += interrupt_cycles;
+        }
+
+        /*
+           0x0000 - 0x0099 initially has boot rom.
+           0x0100 - 0x0103 is entry point of game rom. Once we get there we need to overwrite boot rom with game data.
+           */
+        if self.cpu.pc == 0x0100 {
+            self.overwrite_boot_rom();
         }
+        cycles
     }
 
-    fn update_timers(&mut self, cycles: u32) {
-        self.update_div_timer(cycles);
+    fn update_hardware(&mut self, cycles: u32) {
+        self.cycles_this_frame += cycles;
+        self.update_timers(cycles);
+        self.update_serial(cycles);
 
-        let mut databus_borrow = self.databus.borrow_mut();
+        //In CGB double speed mode PPU and cartridge clock keep their pace while cpu and timers run twice as fast.
+        let realtime_cycles = if self.databus.borrow().double_speed { cycles / 2 } else { cycles };
+        self.databus.borrow_mut().mapper.tick(realtime_cycles);
+        self.ppu.update_graphics(realtime_cycles);
+        self.update_rumble();
+    }
 
-        let TAC = databus_borrow.read_memory(TAC_ADDRESS);
-        let TIMA_ENABLED: bool = ((TAC >> 2) & 1) != 0;
-
-        let TMA = databus_borrow.read_memory(TMA_ADDRESS);
-
-        if TIMA_ENABLED {
-            self.tima_counter += cycles;
-            if self.tima_counter >= self.clock_frequency {
-                let current_TIMA = databus_borrow.read_memory(TIMA_ADDRESS);
-                let result = current_TIMA.overflowing_add(1);
-                if result.1 {
-                    databus_borrow.write_memory(TMA, TIMA_ADDRESS);
-                    drop(databus_borrow);
-                    self.request_interupt(2);
-                }
-                else {
-                    databus_borrow.write_memory(result.0, TIMA_ADDRESS);
-                    drop(databus_borrow);
-                }
-                self.tima_counter = 0;
-                self.update_clock_frequency();
-            }
+    fn update_rumble(&mut self) {
+        let rumble = self.databus.borrow().mapper.rumble();
+        if rumble == self.rumble {
+            return;
         }
-    }
 
-    fn do_interrupts(&mut self) {
-        if self.cpu.IME_enabled {
-            let IE = self.databus.borrow().read_memory(IE_ADDRESS);
-            if IE > 0 {
-                let IF = self.databus.borrow().read_memory(IF_ADDRESS);
-                for i in 0..5 {
-                    let is_enabled = ((IE >> i) & 0x01) != 0;
-                    let is_requested = ((IF >> i) & 0x01) != 0;
-
-                    if is_enabled && is_requested {
-                        self.cpu.IME_enabled = false;
-                        self.cpu.PUSH(self.cpu.pc);
-                        let updated_IF = IF ^ (1 << i);
-                        self.databus.borrow_mut().write_memory(updated_IF, IF_ADDRESS);
-                        match i {
-                            0 => {
-                                //VBLANK interrupt
-                                self.cpu.pc = 0x0040;
-                            }
-
-                            1 => {
-                                //LCD interrupt
-                                self.cpu.pc = 0x0048;
-                            }
-
-                            2 => {
-                                //Serial interrupt
-                                self.cpu.pc = 0x0050;
-                            }
-
-                            3 => {
-                                //Joypad interrupt
-                                self.cpu.pc = 0x0060;
-                            }
-
-                            _ => {
-                                panic!("Non existing interrupt.");
-                            }
-                        }
-                        self.cycles_this_frame = self.cycles_this_frame.wrapping_add(5);
-                    }
-                }
-            }
+        self.rumble = rumble;
+        if let Some(callback) = &mut self.rumble_callback {
+            callback(rumble);
         }
     }
 
-    pub fn load_boot_rom(&mut self) {
-        for (index, byte) in crate::boot_rom::BOOT_ROM.iter().enumerate() {
-            self.databus.borrow_mut().load_boot_rom(index, *byte);
+    /*
+        STOP with armed KEY1 on CGB switches cpu speed and carries on.
+        Otherwise DIV gets reset, LCD goes blank and everything waits for joypad.
+    */
+    fn enter_stop_mode(&mut self) {
+        let mut databus_borrow = self.databus.borrow_mut();
+        if databus_borrow.cgb_mode && databus_borrow.speed_switch_armed {
+            databus_borrow.double_speed = !databus_borrow.double_speed;
+            databus_borrow.speed_switch_armed = false;
+            self.cpu.is_stopped = false;
+            return;
         }
+
+        databus_borrow.timer.write_DIV();
+        drop(databus_borrow);
+        self.ppu.blank();
     }
 
-    fn overwrite_boot_rom(&mut self) {
-        let file = std::fs::read(Path::new(&self.game_rom_path)).unwrap();
+    //Any of currently selected joypad lines pulled low a.k.a. button pressed.
+    fn joypad_line_low(&self) -> bool {
+        (self.databus.borrow().read_memory(0xFF00) & 0x0F) != 0x0F
+    }
 
-        for (index, byte) in file.iter().enumerate() {
-            if index == 0x0100 {
-                break;
-            }
-            self.databus.borrow_mut().load_rom(index, *byte);
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.databus.borrow_mut().interrupts.request(interrupt);
+    }
+
+    fn update_timers(&mut self, cycles: u32) {
+        let overflowed = self.databus.borrow_mut().timer.update(cycles);
+        if overflowed {
+            self.request_interrupt(Interrupt::Timer);
         }
     }
 
-    pub fn load_rom(&mut self) {
-        let file = std::fs::read(Path::new(&self.game_rom_path)).unwrap();
+    fn update_serial(&mut self, cycles: u32) {
+        let transfer_finished = self.databus.borrow_mut().serial.update(cycles);
+        if transfer_finished {
+            self.request_interrupt(Interrupt::Serial);
+        }
+    }
 
-        let cartridge_type = file.get(0x0147).unwrap();
-        let rom_banks = file.get(0x0148).unwrap();
-        let ram_banks = &0x02;
+    pub fn connect_link_peer(&mut self, peer: Box<dyn LinkPeer>) {
+        self.databus.borrow_mut().serial.connect(peer);
+    }
 
-        match cartridge_type {
-            0x00 => {
-            }
+    pub fn disconnect_link_peer(&mut self) -> Box<dyn LinkPeer> {
+        self.databus.borrow_mut().serial.disconnect()
+    }
 
-            0x01..=0x03 => {
-                self.databus.borrow_mut().MBC1 = true;
-            }
+    /*
+        Services highest priority pending interrupt, if IME allows it. Returns cycles taken.
 
-            0x05..=0x06 => {
-                self.databus.borrow_mut().MBC2 = true;
-            }
+        Vector is picked only after high byte of pc got pushed. If that push lands on IE (sp = 0x0000)
+        and disables pending interrupt, dispatch gets cancelled and cpu ends up at 0x0000 instead.
+    */
+    fn do_interrupts(&mut self) -> u32 {
+        if !self.cpu.IME_enabled || !self.cpu.interrupt_pending() {
+            return 0;
+        }
 
-            0x08..=0x0D => {
+        self.cpu.IME_enabled = false;
+
+        let pc = self.cpu.pc;
+        self.cpu.sp = self.cpu.sp.wrapping_sub(1);
+        self.databus.borrow_mut().write_memory((pc >> 8) as u8, self.cpu.sp);
+
+        let interrupt = self.databus.borrow().interrupts.highest_pending();
+
+        self.cpu.sp = self.cpu.sp.wrapping_sub(1);
+        self.databus.borrow_mut().write_memory((pc & 0xFF) as u8, self.cpu.sp);
+
+        match interrupt {
+            Some(interrupt) => {
+                self.databus.borrow_mut().interrupts.acknowledge(interrupt);
+                self.cpu.pc = interrupt.vector();
             }
 
-            _ => {
-                panic!("Not supported cartirdge type.");
+            None => {
+                self.cpu.pc = 0x0000;
             }
         }
 
-        match ram_banks {
+        DISPATCH_CYCLES
+    }
 
-            0x00..=0x01 => {
-            }
+    pub fn load_boot_rom(&mut self) {
+        self.databus.borrow_mut().boot_rom_mapped = true;
+    }
 
-            0x02 => {
-                self.databus.borrow_mut().RAM_banks_count = 1;
-            }
+    fn overwrite_boot_rom(&mut self) {
+        self.databus.borrow_mut().boot_rom_mapped = false;
+    }
 
-            0x03 => {
-                self.databus.borrow_mut().RAM_banks_count = 4;
-            }
+    /*
+        Called with new motor state every time rumble cartridge turns its motor on or off. Games vary strength
+        by switching it many times per frame, so forwarding every call straight to hardware isn't a good idea.
+    */
+    pub fn set_rumble_callback(&mut self, callback: Box<dyn FnMut(bool)>) {
+        self.rumble_callback = Some(callback);
+    }
 
-            0x04 => {
-                self.databus.borrow_mut().RAM_banks_count = 16;
-            }
+    /*
+        Accelerometer input for tilt cartridges (MBC7), ignored by everything else. In g, roughly -1.0 to 1.0,
+        positive x is GameBoy tilted to the right, positive y tilted towards the player.
+    */
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.databus.borrow_mut().mapper.set_tilt(x, y);
+    }
 
-            0x05 => {
-                self.databus.borrow_mut().RAM_banks_count = 8;
-            }
+    //Takes effect on next load_rom(), only matters for cartridges with a clock.
+    pub fn set_rtc_clock(&mut self, rtc_clock: RtcClock) {
+        self.rtc_clock = rtc_clock;
+    }
+
+    pub fn load_rom(&mut self) -> Result<(), LoadError> {
+        let file = fs::read(Path::new(&self.game_rom_path))?;
+        let header = CartridgeHeader::parse(&file)?;
+
+        let mut databus_borrow = self.databus.borrow_mut();
+        databus_borrow.mapper = mapper::from_header(&header, file, self.rtc_clock)?;
+        /*
+            We boot as DMG, where CGB enhanced games run in DMG mode just like plain ones.
+            Only CGB only games get KEY1 and speed switch.
+        */
+        databus_borrow.cgb_mode = header.cgb_support() == CgbSupport::Only;
+        drop(databus_borrow);
+        self.cartridge_header = Some(header);
+        Ok(())
+    }
+
+    //Battery backed cartridge contents, None if cartridge has no battery.
+    pub fn save_battery(&self) -> Option<Vec<u8>> {
+        self.databus.borrow().mapper.save()
+    }
+
+    pub fn restore_battery(&mut self, data: &[u8]) {
+        self.databus.borrow_mut().mapper.restore(data);
+    }
+
+    //Battery save lives next to the rom, game.gb -> game.sav.
+    pub fn save_file_path(&self) -> PathBuf {
+        Path::new(&self.game_rom_path).with_extension("sav")
+    }
 
-            _ => {
-                panic!("Non supported amount of ram banks.");
+    //Missing save file is fine, game just starts with empty RAM. Returns whether anything got loaded.
+    pub fn load_save_file(&mut self) -> io::Result<bool> {
+        match fs::read(self.save_file_path()) {
+            Ok(data) => {
+                self.restore_battery(&data);
+                Ok(true)
             }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error),
         }
+    }
 
-        if *rom_banks > 0x08 {
-            panic!("Non supported amount of rom banks.");
-        }
-        else {
-            self.databus.borrow_mut().ROM_banks_count = u32::pow(2 ,1 + *rom_banks as u32);
-        }
-        
-        let cartridge_ram = vec![0; (self.databus.borrow().RAM_banks_count as u32 * 0x2000) as usize];
-        let cartridge_rom = vec![0; (self.databus.borrow().ROM_banks_count * 0x4000) as usize];
-        self.databus.borrow_mut().cartridge_ram = cartridge_ram;
-        self.databus.borrow_mut().cartridge_rom = cartridge_rom;
-
-        for (index, byte) in file.iter().enumerate() {
-            self.databus.borrow_mut().load_rom(index, *byte);
+    //Does nothing for cartridges without battery.
+    pub fn write_save_file(&self) -> io::Result<()> {
+        match self.save_battery() {
+            Some(data) => fs::write(self.save_file_path(), data),
+            None => Ok(()),
         }
     }
 
+    pub fn cartridge_header(&self) -> Option<&CartridgeHeader> {
+        self.cartridge_header.as_ref()
+    }
+
     pub fn key_pressed(&mut self, key_id: u8) {
         let input_modes = (self.databus.borrow().read_memory(0xFF00) >> 4) & 0x03;
         let dpad_mode: bool = (input_modes & 0x01) == 0;
@@ -304,11 +351,11 @@ impl<'a> GameBoy<'a> {
         self.databus.borrow_mut().joypad_state = self.joypad_state;
 
         if dpad_mode && !pressed_before && key_id < 4 {
-            self.request_interupt(4);
+            self.request_interrupt(Interrupt::Joypad);
         }
 
         if buttons_mode && !pressed_before && key_id > 3 {
-            self.request_interupt(4);
+            self.request_interrupt(Interrupt::Joypad);
         }
     }
 
@@ -317,6 +364,10 @@ impl<'a> GameBoy<'a> {
         self.databus.borrow_mut().joypad_state = self.joypad_state;
     }
 
+    pub fn read_memory(&self, addr: u16) -> u8 {
+        self.databus.borrow().read_memory(addr)
+    }
+
     fn exec_next_instruction(&mut self) -> u8 {
         let instruction_byte = self.databus.borrow().read_memory(self.cpu.pc);
         self.cpu.exec_instruction(instruction_byte)
@@ -0,0 +1,106 @@
+/*
+    Interrupt controller, owns IE (0xFFFF) and IF (0xFF0F).
+    https://gbdev.io/pandocs/Interrupts.html
+
+    Bit / priority, lower bit wins:
+        0 - VBlank  -> 0x0040
+        1 - LCD     -> 0x0048
+        2 - Timer   -> 0x0050
+        3 - Serial  -> 0x0058
+        4 - Joypad  -> 0x0060
+*/
+
+pub const IE_ADDRESS: u16 = 0xFFFF;
+pub const IF_ADDRESS: u16 = 0xFF0F;
+
+//Servicing interrupt takes 5 M-cycles: 2 wait, 2 for pushing pc, 1 for jump.
+pub const DISPATCH_CYCLES: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LcdStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    pub fn bit(self) -> u8 {
+        match self {
+            Interrupt::VBlank => 0,
+            Interrupt::LcdStat => 1,
+            Interrupt::Timer => 2,
+            Interrupt::Serial => 3,
+            Interrupt::Joypad => 4,
+        }
+    }
+
+    pub fn vector(self) -> u16 {
+        0x0040 + self.bit() as u16 * 8
+    }
+}
+
+pub struct InterruptController {
+    IE: u8,
+    IF: u8,
+}
+
+impl Default for InterruptController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InterruptController {
+    pub fn new() -> InterruptController {
+        InterruptController {
+            IE: 0x00,
+            IF: 0x00,
+        }
+    }
+
+    pub fn read_IE(&self) -> u8 {
+        self.IE
+    }
+
+    pub fn write_IE(&mut self, data: u8) {
+        self.IE = data;
+    }
+
+    pub fn read_IF(&self) -> u8 {
+        //Only 5 lower bits exist, rest read as 1.
+        self.IF | 0xE0
+    }
+
+    pub fn write_IF(&mut self, data: u8) {
+        self.IF = data & 0x1F;
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.IF |= 1 << interrupt.bit();
+    }
+
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.IF &= !(1 << interrupt.bit());
+    }
+
+    //Enabled and requested, regardless of IME.
+    pub fn has_pending(&self) -> bool {
+        (self.IE & self.IF & 0x1F) != 0
+    }
+
+    //Highest priority interrupt that's both enabled and requested.
+    pub fn highest_pending(&self) -> Option<Interrupt> {
+        let pending = self.IE & self.IF;
+        Interrupt::ALL.into_iter().find(|interrupt| ((pending >> interrupt.bit()) & 0x01) != 0)
+    }
+}
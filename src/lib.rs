@@ -0,0 +1,44 @@
+#![allow(non_snake_case)]
+#![allow(non_camel_case_types)]
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod gameboy;
+pub mod cpu;
+mod cpu_instructions;
+pub mod ppu;
+mod apu;
+pub mod databus;
+pub mod serial;
+pub mod link;
+pub mod dual;
+pub mod printer;
+pub mod interrupts;
+pub mod timer;
+pub mod pacing;
+pub mod error;
+pub mod cartridge;
+pub mod mapper;
+mod boot_rom;
+
+pub use crate::gameboy::GameBoy;
+pub use crate::dual::DualGameBoy;
+pub use crate::cpu::{Cpu, Registers};
+pub use crate::ppu::Ppu;
+pub use crate::databus::DataBus;
+pub use crate::serial::LinkPeer;
+pub use crate::error::LoadError;
+pub use crate::cartridge::CartridgeHeader;
+
+pub const DEBUG: bool = false;
+
+pub const SCREEN_WIDTH: u32  = 160;
+pub const SCREEN_HEIGHT: u32 = 144;
+
+pub const RIGHT: u8  = 0;
+pub const LEFT: u8   = 1;
+pub const UP: u8     = 2;
+pub const DOWN: u8   = 3;
+pub const A: u8      = 4;
+pub const B: u8      = 5;
+pub const SELECT: u8 = 6;
+pub const START: u8  = 7;
@@ -0,0 +1,185 @@
+/*
+    Link cable between two emulator processes, over TCP or Unix domain socket.
+
+    Every message is 2 bytes, kind and data:
+        CLOCK - sender is master and just clocked out data, other side has to answer with REPLY.
+        REPLY - data that was in slave's SB.
+
+    Answering is done by background thread, so master gets its byte back right away even if other process
+    is between frames (sleeping in frame pacer, paused...). Thread only needs to know if emulated slave is
+    waiting for external clock and what's in its SB, that is shared through `SlaveState`.
+
+    Master still has to wait for REPLY on emulation thread. If it doesn't come within a frame (other side
+    stalled, too slow network...) transfer finishes as if cable was unplugged and game gets 0xFF.
+*/
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use crate::pacing;
+use crate::serial::{LinkPeer, SlaveState};
+
+const CLOCK: u8 = 0x01;
+const REPLY: u8 = 0x02;
+
+const UNIX_PREFIX: &str = "unix:";
+
+type SharedWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+
+pub struct SocketLink {
+    writer: SharedWriter,
+    replies: Receiver<u8>,
+    slave: Arc<Mutex<SlaveState>>,
+}
+
+impl SocketLink {
+    /*
+        Waits for other instance to connect. `address` is either "host:port" or "unix:/path/to/socket".
+        Blocks until someone connects.
+    */
+    pub fn listen(address: &str) -> io::Result<SocketLink> {
+        if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+            return Self::listen_unix(path);
+        }
+
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        Self::from_tcp(stream)
+    }
+
+    pub fn connect(address: &str) -> io::Result<SocketLink> {
+        if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+            return Self::connect_unix(path);
+        }
+
+        Self::from_tcp(TcpStream::connect(address)?)
+    }
+
+    fn from_tcp(stream: TcpStream) -> io::Result<SocketLink> {
+        //Messages are tiny and latency is all that matters.
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+        Ok(Self::from_streams(Box::new(reader), Box::new(stream)))
+    }
+
+    #[cfg(unix)]
+    fn listen_unix(path: &str) -> io::Result<SocketLink> {
+        remove_stale_socket(path)?;
+        let listener = UnixListener::bind(path)?;
+        let (stream, _) = listener.accept()?;
+        let _ = std::fs::remove_file(path);
+        let reader = stream.try_clone()?;
+        Ok(Self::from_streams(Box::new(reader), Box::new(stream)))
+    }
+
+    #[cfg(unix)]
+    fn connect_unix(path: &str) -> io::Result<SocketLink> {
+        let stream = UnixStream::connect(path)?;
+        let reader = stream.try_clone()?;
+        Ok(Self::from_streams(Box::new(reader), Box::new(stream)))
+    }
+
+    #[cfg(not(unix))]
+    fn listen_unix(_path: &str) -> io::Result<SocketLink> {
+        Err(unix_unsupported())
+    }
+
+    #[cfg(not(unix))]
+    fn connect_unix(_path: &str) -> io::Result<SocketLink> {
+        Err(unix_unsupported())
+    }
+
+    fn from_streams(reader: Box<dyn Read + Send>, writer: Box<dyn Write + Send>) -> SocketLink {
+        let writer: SharedWriter = Arc::new(Mutex::new(writer));
+        let slave = Arc::new(Mutex::new(SlaveState::default()));
+        let (reply_sender, replies) = mpsc::channel();
+
+        let thread_writer = Arc::clone(&writer);
+        let thread_slave = Arc::clone(&slave);
+        thread::spawn(move || {
+            let mut reader = reader;
+            let mut message = [0; 2];
+
+            //Ends when other side disconnects, dropping reply_sender makes pending exchange give up right away.
+            while reader.read_exact(&mut message).is_ok() {
+                let connected = match message[0] {
+                    CLOCK => {
+                        let answer = thread_slave.lock().unwrap().clock(message[1]);
+                        send(&thread_writer, REPLY, answer).is_ok()
+                    }
+
+                    //Fails only when SocketLink itself was dropped.
+                    REPLY => reply_sender.send(message[1]).is_ok(),
+
+                    _ => true,
+                };
+
+                if !connected {
+                    break;
+                }
+            }
+        });
+
+        SocketLink {
+            writer,
+            replies,
+            slave,
+        }
+    }
+}
+
+/*
+    Socket file left over from previous run that didn't clean up gets removed. Anything else at that path
+    is most likely a typo (rom, save file...) and stays where it is.
+*/
+#[cfg(unix)]
+fn remove_stale_socket(path: &str) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        Ok(_) => Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{path} already exists and isn't a socket."))),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(not(unix))]
+fn unix_unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "Unix domain sockets aren't available on this platform.")
+}
+
+fn send(writer: &SharedWriter, kind: u8, data: u8) -> io::Result<()> {
+    let mut writer = writer.lock().unwrap();
+    writer.write_all(&[kind, data])?;
+    writer.flush()
+}
+
+impl LinkPeer for SocketLink {
+    fn exchange(&mut self, outgoing: u8) -> u8 {
+        //Late replies to transfers that already timed out.
+        while self.replies.try_recv().is_ok() {}
+
+        if send(&self.writer, CLOCK, outgoing).is_err() {
+            return 0xFF;
+        }
+
+        //Other side normally answers within a socket round trip. Waiting longer than a frame would stall
+        //the frontend, so late reply counts as disconnected cable, same as other side actually disconnecting.
+        self.replies.recv_timeout(pacing::frame_duration()).unwrap_or(0xFF)
+    }
+
+    fn poll_external(&mut self, outgoing: u8) -> Option<u8> {
+        self.slave.lock().unwrap().poll(outgoing)
+    }
+
+    fn ignore_external(&mut self) {
+        self.slave.lock().unwrap().ignore();
+    }
+}
@@ -1,70 +1,531 @@
-#![allow(non_snake_case)]
-#![allow(non_camel_case_types)]
-#![allow(clippy::upper_case_acronyms)]
-
-mod gameboy;
-mod cpu;
-mod cpu_instructions;
-mod ppu;
-mod apu;
-mod databus;
-mod boot_rom;
-
+use std::cell::Cell;
 use std::env;
-use std::fs::metadata;
 use std::path::Path;
+use std::process;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use sdl2::controller::{Axis, GameController};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::Texture;
+use sdl2::GameControllerSubsystem;
+
+use gameooy::dual::{LEFT_SIDE, RIGHT_SIDE};
+use gameooy::link::SocketLink;
+use gameooy::mapper::RtcClock;
+use gameooy::printer::Printer;
+use gameooy::pacing::{self, FramePacer, MIN_SPEED, MAX_SPEED};
+use gameooy::{cartridge, mapper, CartridgeHeader, DualGameBoy, GameBoy, LoadError, SCREEN_WIDTH, SCREEN_HEIGHT};
+use gameooy::{RIGHT, LEFT, UP, DOWN, A, B, SELECT, START};
+
+const SCREEN_SCALE: u32  = 5;
+
+const USAGE: &str = "Correct usage: gameooy [--speed <multiplier>] [--unthrottled] [--rtc-clock <emulated | host>] [--link-listen <address> | --link-connect <address> | --printer <output dir>] <game rom path>\n               gameooy [--speed <multiplier>] [--unthrottled] [--rtc-clock <emulated | host>] --dual <game rom path> [<second game rom path>]\n               gameooy info <game rom path>";
+
+/*
+    Emulation speed hotkeys, game keys are arrows, A, S, Enter and Space.
+        P       - pause / resume
+        N       - advance single frame, pauses if running
+        +, -    - double / halve speed (0.25x - 8x)
+        0       - back to normal speed
+        Tab     - toggle unthrottled, runs as fast as host allows
+        F1, F2  - in dual mode, whether left or right GameBoy gets game keys
+        I, J, K, L - tilt up, left, down, right on tilt cartridges (MBC7), mouse and left stick tilt as well
+*/
+const PAUSE_KEY: Keycode = Keycode::P;
+const FRAME_ADVANCE_KEY: Keycode = Keycode::N;
+const UNTHROTTLED_KEY: Keycode = Keycode::TAB;
+const FOCUS_LEFT_KEY: Keycode = Keycode::F1;
+const FOCUS_RIGHT_KEY: Keycode = Keycode::F2;
+const TILT_UP_KEY: Keycode = Keycode::I;
+const TILT_LEFT_KEY: Keycode = Keycode::J;
+const TILT_DOWN_KEY: Keycode = Keycode::K;
+const TILT_RIGHT_KEY: Keycode = Keycode::L;
+
+//Stick movement smaller than this counts as centred, so worn sticks don't keep taking tilt over from mouse.
+const STICK_DEADZONE: f32 = 0.15;
+
+//How often window title gets refreshed with measured frame rate while unthrottled.
+const FPS_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+//Controller vibration gets refreshed every frame while motor runs, so it stops on its own when emulation pauses.
+const RUMBLE_DURATION_MS: u32 = 100;
+
+//https://pixelcod.itch.io/ghost
+const PALETTE: [[u8; 3]; 4] = [
+    [0xFC, 0xEE, 0xE8],
+    [0xDC, 0x95, 0xA7],
+    [0x71, 0x5A, 0x9B],
+    [0x10, 0x10, 0x18],
+];
+
+//Dual mode texture has screens next to each other, `screen` picks which one to draw into.
+fn draw_frame(texture: &mut Texture, framebuffer: &[u8], screen: usize) {
+    let _ = texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+        let screen_offset = screen * SCREEN_WIDTH as usize * 3;
+        for y in 0..SCREEN_HEIGHT as usize {
+            for x in 0..SCREEN_WIDTH as usize {
+                let colour = PALETTE[framebuffer[y * SCREEN_WIDTH as usize + x] as usize];
+                let offset = y * pitch + screen_offset + x * 3;
+                buffer[offset..offset + 3].copy_from_slice(&colour);
+            }
+        }
+    });
+}
+
+//What's on the other end of link cable. Address is "host:port" for TCP or "unix:<path>" for Unix domain socket.
+enum LinkOption {
+    Listen(String),
+    Connect(String),
+    //Game Boy Printer saving prints into directory.
+    Printer(String),
+}
+
+struct Options {
+    rom_path: String,
+    speed: f64,
+    unthrottled: bool,
+    //What cartridge clock (MBC3) counts time with.
+    rtc_clock: RtcClock,
+    link: Option<LinkOption>,
+    //Run two GameBoys cabled together, right one runs second rom if given.
+    dual: bool,
+    second_rom_path: Option<String>,
+}
+
+fn check_rom_path(rom_path: &str) -> Result<(), String> {
+    if !Path::new(rom_path).exists() {
+        return Err(format!("Non existing file path: {rom_path}"));
+    }
+    if Path::new(rom_path).is_dir() {
+        return Err(format!("Provided file is a directory: {rom_path}"));
+    }
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut rom_path: Option<String> = None;
+    let mut speed = 1.0;
+    let mut unthrottled = false;
+    let mut rtc_clock = RtcClock::Emulated;
+    let mut link = None;
+    let mut dual = false;
+    let mut second_rom_path: Option<String> = None;
+
+    let mut args_iter = args.iter().skip(1);
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--speed" => {
+                let value = args_iter.next().ok_or("Missing value for --speed.")?;
+                speed = value.trim_end_matches('x').parse().map_err(|_| format!("Invalid speed: {value}"))?;
+                if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+                    return Err(format!("Speed must be between {MIN_SPEED}x and {MAX_SPEED}x, got: {value}"));
+                }
+            }
+
+            "--unthrottled" => {
+                unthrottled = true;
+            }
+
+            "--rtc-clock" => {
+                let value = args_iter.next().ok_or("Missing value for --rtc-clock.")?;
+                rtc_clock = match value.as_str() {
+                    "emulated" => RtcClock::Emulated,
+                    "host" => RtcClock::Host,
+                    _ => return Err(format!("Invalid clock, expected emulated or host: {value}")),
+                };
+            }
+
+            "--link-listen" | "--link-connect" | "--printer" => {
+                if link.is_some() {
+                    return Err(String::from("Only one of --link-listen, --link-connect and --printer can be used."));
+                }
+                let value = args_iter.next().ok_or(format!("Missing value for {arg}."))?.clone();
+                link = Some(match arg.as_str() {
+                    "--link-listen" => LinkOption::Listen(value),
+                    "--link-connect" => LinkOption::Connect(value),
+                    _ => LinkOption::Printer(value),
+                });
+            }
+
+            "--dual" => {
+                dual = true;
+            }
+
+            _ if rom_path.is_none() && !arg.starts_with("--") => {
+                rom_path = Some(arg.clone());
+            }
+
+            _ if second_rom_path.is_none() && !arg.starts_with("--") => {
+                second_rom_path = Some(arg.clone());
+            }
+
+            _ => {
+                return Err(format!("Unknown argument: {arg}"));
+            }
+        }
+    }
+
+    let rom_path = rom_path.ok_or("Missing game rom path.")?;
+    check_rom_path(&rom_path)?;
+
+    if let Some(second_rom_path) = &second_rom_path {
+        if !dual {
+            return Err(format!("Second rom can only be used with --dual: {second_rom_path}"));
+        }
+        check_rom_path(second_rom_path)?;
+    }
+    if dual && link.is_some() {
+        return Err(String::from("--dual already has its own link cable, it can't be used with --link-listen, --link-connect or --printer."));
+    }
+
+    Ok(Options {
+        rom_path,
+        speed,
+        unthrottled,
+        rtc_clock,
+        link,
+        dual,
+        second_rom_path,
+    })
+}
+
+/*
+    Either single GameBoy or two of them cabled together, frontend doesn't care much which one it runs.
+*/
+enum Emulator {
+    Single(Box<GameBoy>),
+    Dual(Box<DualGameBoy>),
+}
+
+impl Emulator {
+    fn update(&mut self) {
+        match self {
+            Emulator::Single(gameboy) => gameboy.update(),
+            Emulator::Dual(dual) => dual.update(),
+        }
+    }
+
+    fn screens(&self) -> usize {
+        match self {
+            Emulator::Single(_) => 1,
+            Emulator::Dual(_) => 2,
+        }
+    }
+
+    fn gameboy(&self, screen: usize) -> &GameBoy {
+        match self {
+            Emulator::Single(gameboy) => gameboy,
+            Emulator::Dual(dual) => dual.gameboy(screen),
+        }
+    }
+
+    fn gameboy_mut(&mut self, screen: usize) -> &mut GameBoy {
+        match self {
+            Emulator::Single(gameboy) => gameboy,
+            Emulator::Dual(dual) => dual.gameboy_mut(screen),
+        }
+    }
+}
+
+fn load_gameboy(rom_path: &str, rtc_clock: RtcClock) -> GameBoy {
+    let mut gameboy = GameBoy::new(rom_path.to_string());
+    gameboy.set_rtc_clock(rtc_clock);
+    if let Err(error) = gameboy.load_rom() {
+        exit_with_error(&error.to_string());
+    }
+    gameboy.load_boot_rom();
+
+    if let Err(error) = gameboy.load_save_file() {
+        eprintln!("Can't read save file {}: {error}", gameboy.save_file_path().display());
+    }
+    gameboy
+}
+
+//Printer only reports saved prints through callback, it's up to us to tell the user.
+fn new_printer(output_dir: &str) -> Printer {
+    let mut printer = Printer::new(output_dir);
+    let output_dir = output_dir.to_string();
+    printer.set_print_callback(Box::new(move |result| match result {
+        Ok(path) => println!("Printed {}", path.display()),
+        Err(error) => eprintln!("Printer couldn't save image to {output_dir}: {error}"),
+    }));
+    printer
+}
+
+//Same rom in both sides of dual mode shares one save file, left side wins.
+fn write_save_files(emulator: &Emulator) {
+    let mut written = Vec::new();
+    for screen in 0..emulator.screens() {
+        let gameboy = emulator.gameboy(screen);
+        let path = gameboy.save_file_path();
+        if written.contains(&path) {
+            continue;
+        }
+
+        if let Err(error) = gameboy.write_save_file() {
+            eprintln!("Can't write save file {}: {error}", path.display());
+        }
+        written.push(path);
+    }
+}
+
+/*
+    Rumble cartridge motor as reported by GameBoy callback. Games set strength by switching motor on and off
+    many times per frame, so frontend only asks once per frame whether it ran at all.
+*/
+#[derive(Default)]
+struct Motor {
+    on: Cell<bool>,
+    ran_this_frame: Cell<bool>,
+}
+
+impl Motor {
+    fn connect(gameboy: &mut GameBoy) -> Rc<Motor> {
+        let motor = Rc::new(Motor::default());
+        let callback_motor = Rc::clone(&motor);
+        gameboy.set_rumble_callback(Box::new(move |on| {
+            callback_motor.on.set(on);
+            if on {
+                callback_motor.ran_this_frame.set(true);
+            }
+        }));
+        motor
+    }
+
+    fn take_frame(&self) -> bool {
+        self.on.get() | self.ran_this_frame.replace(false)
+    }
+}
+
+/*
+    Tilt for accelerometer cartridges in g, positive x is right and positive y towards the player.
+    Keyboard, mouse and left stick each set it, whichever got used last wins.
+*/
+#[derive(Default)]
+struct Tilt {
+    x: f32,
+    y: f32,
 
-use crate::gameboy::GameBoy;
+    keys_held: [bool; 4],
+    stick_x: f32,
+    stick_y: f32,
+}
+
+impl Tilt {
+    fn key(&mut self, keycode: Keycode, pressed: bool) {
+        let index = match keycode {
+            TILT_UP_KEY => 0,
+            TILT_LEFT_KEY => 1,
+            TILT_DOWN_KEY => 2,
+            _ => 3,
+        };
+        self.keys_held[index] = pressed;
+
+        let [up, left, down, right] = self.keys_held.map(|held| held as i8 as f32);
+        self.x = right - left;
+        self.y = down - up;
+    }
+
+    //Mouse tilts by how far it is from screen centre, screen edge is 1g.
+    fn mouse(&mut self, offset_x: i32, offset_y: i32, half_width: u32, half_height: u32) {
+        self.x = (offset_x as f32 / half_width as f32).clamp(-1.0, 1.0);
+        self.y = (offset_y as f32 / half_height as f32).clamp(-1.0, 1.0);
+    }
+
+    fn stick(&mut self, axis: Axis, value: i16) {
+        let was_centred = self.stick_x.abs() < STICK_DEADZONE && self.stick_y.abs() < STICK_DEADZONE;
+        let value = value as f32 / i16::MAX as f32;
+        match axis {
+            Axis::LeftX => self.stick_x = value,
+            Axis::LeftY => self.stick_y = value,
+            _ => return,
+        }
+
+        let centred = self.stick_x.abs() < STICK_DEADZONE && self.stick_y.abs() < STICK_DEADZONE;
+        if centred && was_centred {
+            return;
+        }
+        if centred {
+            self.x = 0.0;
+            self.y = 0.0;
+        }
+        else {
+            self.x = self.stick_x.clamp(-1.0, 1.0);
+            self.y = self.stick_y.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+//First connected controller, rumble goes there.
+fn open_controller(subsystem: &GameControllerSubsystem) -> Option<GameController> {
+    let joysticks = subsystem.num_joysticks().unwrap_or(0);
+    (0..joysticks)
+        .filter(|index| subsystem.is_game_controller(*index))
+        .find_map(|index| subsystem.open(index).ok())
+}
+
+//e.g. "GameOoy - TETRIS [2x] [paused]"
+fn window_title(base: &str, pacer: &FramePacer, paused: bool, measured_fps: Option<f64>) -> String {
+    let mut title = String::from(base);
+    if pacer.is_unthrottled() {
+        match measured_fps {
+            Some(fps) => title.push_str(&format!(" [unthrottled {fps:.0} fps]")),
+            None => title.push_str(" [unthrottled]"),
+        }
+    }
+    else if pacer.speed() != 1.0 {
+        title.push_str(&format!(" [{}x]", pacer.speed()));
+    }
+    if paused {
+        title.push_str(" [paused]");
+    }
+    title
+}
 
-pub const DEBUG: bool = false;
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{message}");
+    process::exit(1);
+}
+
+fn ok_or_bad(ok: bool) -> &'static str {
+    if ok {
+        "OK"
+    }
+    else {
+        "BAD"
+    }
+}
+
+//e.g. "32 KiB, 2 banks", unknown size codes are reported instead of failing.
+fn describe_size(size: Option<usize>, banks: Option<usize>) -> String {
+    match (size, banks) {
+        (Some(size), Some(banks)) => format!("{} KiB, {banks} banks", size / 1024),
+        _ => String::from("unsupported"),
+    }
+}
+
+fn print_rom_info(rom_path: &str) -> Result<(), LoadError> {
+    let rom = std::fs::read(rom_path)?;
+    let header = CartridgeHeader::parse(&rom)?;
+
+    let header_checksum = cartridge::header_checksum(&rom);
+    let global_checksum = cartridge::global_checksum(&rom);
+
+    let mapper_supported = mapper::is_supported(header.cartridge_type.mapper);
+    let size_matches = header.rom_size().is_some_and(|size| rom.len() <= size);
 
-pub const SCREEN_WIDTH: u32  = 160;
-pub const SCREEN_HEIGHT: u32 = 144;
-pub const SCREEN_SCALE: u32  = 5;
+    println!("Title:             {}", header.title);
+    println!("Manufacturer code: {}", header.manufacturer_code.as_deref().unwrap_or("-"));
+    println!("CGB flag:          {:#04x} {:?}", header.cgb_flag, header.cgb_support());
+    println!("SGB flag:          {:#04x} {}", header.sgb_flag, if header.supports_sgb() { "supported" } else { "not supported" });
+    println!("Old licensee code: {:#04x}", header.old_licensee_code);
+    println!("New licensee code: {}", header.new_licensee_code);
+    println!("Cartridge type:    {:#04x} {}", header.cartridge_type.code, header.cartridge_type);
+    println!("ROM size:          {:#04x} {}", header.rom_size_code, describe_size(header.rom_size(), header.rom_banks()));
+    println!("RAM size:          {:#04x} {}", header.ram_size_code, describe_size(header.ram_size(), header.ram_banks()));
+    println!("Destination:       {:#04x} {}", header.destination_code, if header.destination_code == 0 { "Japan" } else { "Overseas" });
+    println!("Version:           {:#04x}", header.version);
+    println!("Logo:              {}", ok_or_bad(header.logo_valid()));
+    println!("Header checksum:   {:#04x} {} (computed {:#04x})", header.header_checksum, ok_or_bad(header.header_checksum == header_checksum), header_checksum);
+    println!("Global checksum:   {:#06x} {} (computed {:#06x})", header.global_checksum, ok_or_bad(header.global_checksum == global_checksum), global_checksum);
+    println!("File size:         {} KiB {}", rom.len() / 1024, ok_or_bad(size_matches));
+    println!("Mapper supported:  {}", if mapper_supported { "yes" } else { "no" });
 
-pub const RIGHT: u8  = 0;
-pub const LEFT: u8   = 1;
-pub const UP: u8     = 2;
-pub const DOWN: u8   = 3;
-pub const A: u8      = 4;
-pub const B: u8      = 5;
-pub const SELECT: u8 = 6;
-pub const START: u8  = 7;
+    Ok(())
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != 2 {
-        panic!("Correct usage: gameooy <game rom path>")
+    if args.len() == 3 && args[1] == "info" {
+        if let Err(error) = print_rom_info(&args[2]) {
+            exit_with_error(&error.to_string());
+        }
+        return;
     }
-    
-    if !Path::new(&args[1]).exists() {
-        panic!("Non existing faile path.");
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => exit_with_error(&format!("{message}\n{USAGE}")),
+    };
+
+    let mut gameboy = load_gameboy(&options.rom_path, options.rtc_clock);
+
+    match &options.link {
+        Some(LinkOption::Listen(address)) => {
+            println!("Waiting for link cable partner on {address}...");
+            match SocketLink::listen(address) {
+                Ok(link) => gameboy.connect_link_peer(Box::new(link)),
+                Err(error) => exit_with_error(&format!("Can't listen on {address}: {error}")),
+            }
+        }
+
+        Some(LinkOption::Connect(address)) => {
+            match SocketLink::connect(address) {
+                Ok(link) => gameboy.connect_link_peer(Box::new(link)),
+                Err(error) => exit_with_error(&format!("Can't connect to {address}: {error}")),
+            }
+        }
+
+        Some(LinkOption::Printer(output_dir)) => {
+            gameboy.connect_link_peer(Box::new(new_printer(output_dir)));
+        }
+
+        None => {}
     }
 
-    let file_metadata = metadata(Path::new(&args[1])).unwrap();
+    let base_title = match gameboy.cartridge_header() {
+        Some(header) if !header.title.is_empty() => format!("GameOoy - {}", header.title),
+        _ => String::from("GameOoy"),
+    };
 
-    if file_metadata.is_dir() {
-        panic!("Provided file is a directory.");
+    let mut emulator = if options.dual {
+        let second = load_gameboy(options.second_rom_path.as_deref().unwrap_or(&options.rom_path), options.rtc_clock);
+        Emulator::Dual(Box::new(DualGameBoy::new(gameboy, second)))
     }
+    else {
+        Emulator::Single(Box::new(gameboy))
+    };
+    //GameBoy that gets game keys.
+    let mut focus = LEFT_SIDE;
+
+    let motors: Vec<Rc<Motor>> = (0..emulator.screens())
+        .map(|screen| Motor::connect(emulator.gameboy_mut(screen)))
+        .collect();
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem.window("GameOoy", SCREEN_WIDTH * SCREEN_SCALE, SCREEN_HEIGHT * SCREEN_SCALE)
+    let controller_subsystem = sdl_context.game_controller().unwrap();
+    let mut controller = open_controller(&controller_subsystem);
+    let mut rumbling = false;
+    let mut tilt = Tilt::default();
+
+    let mut pacer = FramePacer::new();
+    pacer.set_speed(options.speed);
+    pacer.set_unthrottled(options.unthrottled);
+
+    let window = video_subsystem.window(&window_title(&base_title, &pacer, false, None), SCREEN_WIDTH * SCREEN_SCALE * emulator.screens() as u32, SCREEN_HEIGHT * SCREEN_SCALE)
         .position_centered()
         .build()
         .unwrap();
     let mut canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, SCREEN_WIDTH * emulator.screens() as u32, SCREEN_HEIGHT)
+        .unwrap();
     let mut event_pump = sdl_context.event_pump().unwrap();
 
-    let mut gameboy = GameBoy::new(&mut canvas, args[1].clone());
-    gameboy.load_rom();
-    gameboy.load_boot_rom();
-
     let mut running: bool = true;
+    let mut paused: bool = false;
+    let mut advance_frame: bool = false;
+    let mut title_changed: bool = false;
+
+    let mut fps_frames: u32 = 0;
+    let mut fps_since = Instant::now();
 
     while running {
         for event in event_pump.poll_iter() {
@@ -74,61 +535,171 @@ fn main() {
                     running = false;
                 },
 
+                Event::ControllerDeviceAdded { which, .. } if controller.is_none() => {
+                    controller = controller_subsystem.open(which).ok();
+                }
+                Event::ControllerDeviceRemoved { .. } => {
+                    controller = open_controller(&controller_subsystem);
+                }
+
+                Event::KeyDown { keycode: Some(PAUSE_KEY), repeat: false, .. } => {
+                    paused = !paused;
+                    pacer.reset();
+                    title_changed = true;
+                }
+                Event::KeyDown { keycode: Some(FRAME_ADVANCE_KEY), .. } => {
+                    paused = true;
+                    advance_frame = true;
+                    title_changed = true;
+                }
+                Event::KeyDown { keycode: Some(Keycode::EQUALS | Keycode::PLUS | Keycode::KP_PLUS), repeat: false, .. } => {
+                    pacer.set_speed(pacer.speed() * 2.0);
+                    title_changed = true;
+                }
+                Event::KeyDown { keycode: Some(Keycode::MINUS | Keycode::KP_MINUS), repeat: false, .. } => {
+                    pacer.set_speed(pacer.speed() / 2.0);
+                    title_changed = true;
+                }
+                Event::KeyDown { keycode: Some(Keycode::NUM_0 | Keycode::KP_0), repeat: false, .. } => {
+                    pacer.set_speed(1.0);
+                    title_changed = true;
+                }
+                Event::KeyDown { keycode: Some(keycode @ (FOCUS_LEFT_KEY | FOCUS_RIGHT_KEY)), repeat: false, .. } if emulator.screens() == 2 => {
+                    //Keys held on previous GameBoy would otherwise stay pressed forever.
+                    for key in [RIGHT, LEFT, UP, DOWN, A, B, SELECT, START] {
+                        emulator.gameboy_mut(focus).key_released(key);
+                    }
+                    emulator.gameboy_mut(focus).set_tilt(0.0, 0.0);
+                    focus = if keycode == FOCUS_LEFT_KEY { LEFT_SIDE } else { RIGHT_SIDE };
+                }
+                Event::KeyDown { keycode: Some(UNTHROTTLED_KEY), repeat: false, .. } => {
+                    pacer.set_unthrottled(!pacer.is_unthrottled());
+                    fps_frames = 0;
+                    fps_since = Instant::now();
+                    title_changed = true;
+                }
+
+                Event::KeyDown { keycode: Some(keycode @ (TILT_UP_KEY | TILT_LEFT_KEY | TILT_DOWN_KEY | TILT_RIGHT_KEY)), .. } => {
+                    tilt.key(keycode, true);
+                }
+                Event::KeyUp { keycode: Some(keycode @ (TILT_UP_KEY | TILT_LEFT_KEY | TILT_DOWN_KEY | TILT_RIGHT_KEY)), .. } => {
+                    tilt.key(keycode, false);
+                }
+                Event::MouseMotion { x, y, .. } => {
+                    let half_width = SCREEN_WIDTH * SCREEN_SCALE / 2;
+                    let half_height = SCREEN_HEIGHT * SCREEN_SCALE / 2;
+                    let centre_x = (2 * focus as u32 + 1) * half_width;
+                    tilt.mouse(x - centre_x as i32, y - half_height as i32, half_width, half_height);
+                }
+                Event::ControllerAxisMotion { axis, value, .. } => {
+                    tilt.stick(axis, value);
+                }
+
+                ////
+
                 Event::KeyDown { keycode: Some(Keycode::UP), .. } => {
-                    gameboy.key_pressed(UP);
+                    emulator.gameboy_mut(focus).key_pressed(UP);
                 }
                 Event::KeyDown { keycode: Some(Keycode::DOWN), .. } => {
-                    gameboy.key_pressed(DOWN);
+                    emulator.gameboy_mut(focus).key_pressed(DOWN);
                 }
                 Event::KeyDown { keycode: Some(Keycode::RIGHT), .. } => {
-                    gameboy.key_pressed(RIGHT);
+                    emulator.gameboy_mut(focus).key_pressed(RIGHT);
                 }
                 Event::KeyDown { keycode: Some(Keycode::LEFT), .. } => {
-                    gameboy.key_pressed(LEFT);
+                    emulator.gameboy_mut(focus).key_pressed(LEFT);
                 }
                 Event::KeyDown { keycode: Some(Keycode::A), .. } => {
-                    gameboy.key_pressed(A);
+                    emulator.gameboy_mut(focus).key_pressed(A);
                 }
                 Event::KeyDown { keycode: Some(Keycode::S), .. } => {
-                    gameboy.key_pressed(B);
+                    emulator.gameboy_mut(focus).key_pressed(B);
                 }
                 Event::KeyDown { keycode: Some(Keycode::RETURN), .. } => {
-                    gameboy.key_pressed(SELECT);
+                    emulator.gameboy_mut(focus).key_pressed(SELECT);
                 }
                 Event::KeyDown { keycode: Some(Keycode::SPACE), .. } => {
-                    gameboy.key_pressed(START);
+                    emulator.gameboy_mut(focus).key_pressed(START);
                 }
 
                 ////
 
                 Event::KeyUp { keycode: Some(Keycode::UP), .. } => {
-                    gameboy.key_released(UP);
+                    emulator.gameboy_mut(focus).key_released(UP);
                 }
                 Event::KeyUp { keycode: Some(Keycode::DOWN), .. } => {
-                    gameboy.key_released(DOWN);
+                    emulator.gameboy_mut(focus).key_released(DOWN);
                 }
                 Event::KeyUp { keycode: Some(Keycode::RIGHT), .. } => {
-                    gameboy.key_released(RIGHT);
+                    emulator.gameboy_mut(focus).key_released(RIGHT);
                 }
                 Event::KeyUp { keycode: Some(Keycode::LEFT), .. } => {
-                    gameboy.key_released(LEFT);
+                    emulator.gameboy_mut(focus).key_released(LEFT);
                 }
                 Event::KeyUp { keycode: Some(Keycode::A), .. } => {
-                    gameboy.key_released(A);
+                    emulator.gameboy_mut(focus).key_released(A);
                 }
                 Event::KeyUp { keycode: Some(Keycode::S), .. } => {
-                    gameboy.key_released(B);
+                    emulator.gameboy_mut(focus).key_released(B);
                 }
                 Event::KeyUp { keycode: Some(Keycode::RETURN), .. } => {
-                    gameboy.key_released(SELECT);
+                    emulator.gameboy_mut(focus).key_released(SELECT);
                 }
                 Event::KeyUp { keycode: Some(Keycode::SPACE), .. } => {
-                    gameboy.key_released(START);
+                    emulator.gameboy_mut(focus).key_released(START);
                 }
 
                 _ => {}
             }
         }
-        gameboy.update();
+
+        if title_changed {
+            let _ = canvas.window_mut().set_title(&window_title(&base_title, &pacer, paused, None));
+            title_changed = false;
+        }
+
+        //Nothing to emulate, just keep polling input without burning cpu.
+        if paused && !advance_frame {
+            thread::sleep(pacing::frame_duration());
+            continue;
+        }
+        advance_frame = false;
+
+        emulator.gameboy_mut(focus).set_tilt(tilt.x, tilt.y);
+        emulator.update();
+
+        //Every motor has to be asked so none of them carries this frame over to next one.
+        let motor_ran = motors.iter().fold(false, |ran, motor| motor.take_frame() | ran);
+        if let Some(controller) = &mut controller {
+            if motor_ran {
+                let _ = controller.set_rumble(0xFFFF, 0xFFFF, RUMBLE_DURATION_MS);
+            }
+            else if rumbling {
+                let _ = controller.set_rumble(0, 0, 0);
+            }
+        }
+        rumbling = motor_ran;
+
+        for screen in 0..emulator.screens() {
+            draw_frame(&mut texture, emulator.gameboy(screen).ppu.framebuffer(), screen);
+        }
+        canvas.clear();
+        let _ = canvas.copy(&texture, None, None);
+        canvas.present();
+
+        if pacer.is_unthrottled() {
+            fps_frames += 1;
+            let elapsed = fps_since.elapsed();
+            if elapsed >= FPS_REPORT_INTERVAL {
+                let fps = fps_frames as f64 / elapsed.as_secs_f64();
+                let _ = canvas.window_mut().set_title(&window_title(&base_title, &pacer, paused, Some(fps)));
+                fps_frames = 0;
+                fps_since = Instant::now();
+            }
+        }
+
+        pacer.wait();
     }
+
+    write_save_files(&emulator);
 }
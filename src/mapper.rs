@@ -0,0 +1,116 @@
+/*
+    Cartridge hardware behind 0x0000 - 0x7FFF and 0xA000 - 0xBFFF. https://gbdev.io/pandocs/MBCs.html
+
+    Bus only forwards accesses in those ranges, everything about banking lives in the mapper picked from
+    cartridge header at load time. Supporting new cartridge type means new implementation here and an arm
+    in `from_header`, bus doesn't change.
+*/
+
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc7;
+mod rom_only;
+
+use crate::cartridge::{CartridgeHeader, MapperKind};
+use crate::error::LoadError;
+
+pub use mbc1::Mbc1;
+pub use mbc2::Mbc2;
+pub use mbc3::{Mbc3, RtcClock};
+pub use mbc5::Mbc5;
+pub use mbc7::Mbc7;
+pub use rom_only::RomOnly;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+pub trait Mapper {
+    //0x0000 - 0x7FFF
+    fn read_rom(&self, addr: u16) -> u8;
+
+    //0xA000 - 0xBFFF, disabled or missing RAM reads 0xFF.
+    fn read_ram(&self, addr: u16) -> u8;
+    fn write_ram(&mut self, addr: u16, data: u8);
+
+    //Writes to 0x0000 - 0x7FFF, rom itself is read only so these only set mapper registers.
+    fn write_control(&mut self, addr: u16, data: u8);
+
+    //Battery backed contents (RAM, clock...). None when cartridge has no battery.
+    fn save(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    //Loads what save() returned earlier. Data from a different cartridge may not fit, take what fits.
+    fn restore(&mut self, _data: &[u8]) {}
+
+    //Emulated cycles since last call at normal speed rate, also in CGB double speed. For mappers with their own clock.
+    fn tick(&mut self, _cycles: u32) {}
+
+    //Whether rumble motor is running right now.
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    //Accelerometer input in g, for cartridges that have one.
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+}
+
+pub fn is_supported(mapper: MapperKind) -> bool {
+    matches!(
+        mapper,
+        MapperKind::RomOnly | MapperKind::MBC1 | MapperKind::MBC2 | MapperKind::MBC3 | MapperKind::MBC5 | MapperKind::MBC7
+    )
+}
+
+//`rtc_clock` is what MBC3 clock counts time with, other mappers ignore it.
+pub fn from_header(header: &CartridgeHeader, mut rom: Vec<u8>, rtc_clock: RtcClock) -> Result<Box<dyn Mapper>, LoadError> {
+    let rom_size = header.rom_size().ok_or(LoadError::UnsupportedRomSize(header.rom_size_code))?;
+    let ram_size = header.ram_size().ok_or(LoadError::UnsupportedRamSize(header.ram_size_code))?;
+
+    if rom.len() > rom_size {
+        return Err(LoadError::RomSizeMismatch { header: rom_size, file: rom.len() });
+    }
+    //Short dumps are padded up to size from header, missing part reads like unconnected bus.
+    rom.resize(rom_size, 0xFF);
+
+    let cartridge_type = header.cartridge_type;
+    let mapper: Box<dyn Mapper> = match cartridge_type.mapper {
+        MapperKind::RomOnly => {
+            Box::new(RomOnly::new(rom, ram_size, cartridge_type.battery))
+        }
+
+        MapperKind::MBC1 => {
+            Box::new(Mbc1::new(rom, ram_size, cartridge_type.battery))
+        }
+
+        MapperKind::MBC2 => {
+            Box::new(Mbc2::new(rom, cartridge_type.battery))
+        }
+
+        MapperKind::MBC3 => {
+            Box::new(Mbc3::new(rom, ram_size, cartridge_type.battery, cartridge_type.timer, rtc_clock))
+        }
+
+        MapperKind::MBC5 => {
+            Box::new(Mbc5::new(rom, ram_size, cartridge_type.battery, cartridge_type.rumble))
+        }
+
+        MapperKind::MBC7 => {
+            Box::new(Mbc7::new(rom))
+        }
+
+        _ => {
+            return Err(LoadError::UnsupportedMapper(cartridge_type.code));
+        }
+    };
+
+    Ok(mapper)
+}
+
+//Copies as much of `data` into `ram` as fits.
+fn restore_ram(ram: &mut [u8], data: &[u8]) {
+    let length = ram.len().min(data.len());
+    ram[..length].copy_from_slice(&data[..length]);
+}
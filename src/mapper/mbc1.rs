@@ -0,0 +1,160 @@
+/*
+    MBC1, up to 2MiB rom and 32KiB RAM. https://gbdev.io/pandocs/MBC1.html
+
+    0x0000 - 0x1FFF - RAMG, 0x0A in lower nibble enables RAM.
+    0x2000 - 0x3FFF - BANK1, 5 bits. Lower rom bank bits, 0 is turned into 1.
+    0x4000 - 0x5FFF - BANK2, 2 bits. Upper rom bank bits (1MiB+ roms) or RAM bank (32KiB RAM).
+    0x6000 - 0x7FFF - MODE, 1 bit. In mode 1 BANK2 also applies to 0x0000 - 0x3FFF and RAM.
+
+    Rom bank number is BANK2 << 5 | BANK1 and wraps around to actual rom size.
+
+    MBC1M multicarts (several 256KiB games on one 1MiB cart) have BANK1 bit 4 not connected,
+    so BANK2 lands on bits 4 - 5 instead. Each game has its own header, that's how we recognise them.
+*/
+
+use crate::cartridge;
+
+use super::{restore_ram, Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+const MULTICART_ROM_BANKS: usize = 64;
+const MULTICART_GAME_BANKS: usize = 16;
+
+pub struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    battery: bool,
+
+    rom_banks: usize,
+    ram_banks: usize,
+
+    ram_enabled: bool,
+    BANK1: u8,
+    BANK2: u8,
+    MODE: u8,
+
+    //Bits BANK2 gets shifted by, 5 normally, 4 on multicarts.
+    bank2_shift: u8,
+}
+
+impl Mbc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, battery: bool) -> Mbc1 {
+        let rom_banks = rom.len() / ROM_BANK_SIZE;
+        //2KiB RAM still takes one bank, just mirrored.
+        let ram_banks = (ram_size / RAM_BANK_SIZE).max(1);
+        let bank2_shift = if is_multicart(&rom) { 4 } else { 5 };
+
+        Mbc1 {
+            rom,
+            ram: vec![0; ram_size],
+            battery,
+
+            rom_banks,
+            ram_banks,
+
+            ram_enabled: false,
+            BANK1: 0x01,
+            BANK2: 0x00,
+            MODE: 0,
+
+            bank2_shift,
+        }
+    }
+
+    fn bank1_mask(&self) -> u8 {
+        if self.bank2_shift == 4 {
+            0x0F
+        }
+        else {
+            0x1F
+        }
+    }
+
+    fn read_bank(&self, bank: usize, addr: u16) -> u8 {
+        //Bank pins that go past rom size aren't connected, bank number just wraps around.
+        let bank = bank % self.rom_banks.max(1);
+        self.rom[bank * ROM_BANK_SIZE + (addr as usize & (ROM_BANK_SIZE - 1))]
+    }
+
+    fn ram_address(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+
+        let bank = if self.MODE == 1 { self.BANK2 as usize } else { 0 };
+        let bank = bank & (self.ram_banks - 1);
+        Some((bank * RAM_BANK_SIZE + (addr - 0xA000) as usize) % self.ram.len())
+    }
+}
+
+/*
+    Multicarts are 1MiB and every 256KiB game starts with its own header, menu in first one.
+    Checking that second game has valid logo is enough to tell them apart from regular 1MiB MBC1 roms.
+*/
+fn is_multicart(rom: &[u8]) -> bool {
+    if rom.len() != MULTICART_ROM_BANKS * ROM_BANK_SIZE {
+        return false;
+    }
+
+    let logo_start = MULTICART_GAME_BANKS * ROM_BANK_SIZE + 0x0104;
+    let logo = cartridge::nintendo_logo();
+    rom[logo_start..logo_start + logo.len()] == *logo
+}
+
+impl Mapper for Mbc1 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        let upper_bits = (self.BANK2 as usize) << self.bank2_shift;
+
+        if addr < 0x4000 {
+            let bank = if self.MODE == 1 { upper_bits } else { 0 };
+            return self.read_bank(bank, addr);
+        }
+
+        let bank = upper_bits | (self.BANK1 & self.bank1_mask()) as usize;
+        self.read_bank(bank, addr)
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        match self.ram_address(addr) {
+            Some(address) => self.ram[address],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, data: u8) {
+        if let Some(address) = self.ram_address(addr) {
+            self.ram[address] = data;
+        }
+    }
+
+    fn write_control(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = data & 0x0F == 0x0A;
+            }
+
+            0x2000..=0x3FFF => {
+                //Zero check looks at all 5 bits, even on multicarts where bit 4 goes nowhere.
+                self.BANK1 = data & 0x1F;
+                if self.BANK1 == 0 {
+                    self.BANK1 = 1;
+                }
+            }
+
+            0x4000..=0x5FFF => {
+                self.BANK2 = data & 0x03;
+            }
+
+            _ => {
+                self.MODE = data & 0x01;
+            }
+        }
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        (self.battery && !self.ram.is_empty()).then(|| self.ram.clone())
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram, data);
+    }
+}
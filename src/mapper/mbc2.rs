@@ -0,0 +1,95 @@
+/*
+    MBC2, up to 256KiB rom and 512 x 4 bit RAM built into the mapper chip. https://gbdev.io/pandocs/MBC2.html
+
+    0x0000 - 0x3FFF - register select is address bit 8:
+        clear - RAMG, 0x0A in lower nibble enables RAM.
+        set   - ROMB, 4 bit rom bank, 0 is turned into 1.
+
+    RAM only has 9 address lines, so 512 nibbles repeat over whole 0xA000 - 0xBFFF. Upper nibble isn't
+    connected and reads as 1s.
+*/
+
+use super::{restore_ram, Mapper, ROM_BANK_SIZE};
+
+const RAM_SIZE: usize = 512;
+
+pub struct Mbc2 {
+    rom: Vec<u8>,
+    //One nibble per byte, upper nibble always 0 in here.
+    ram: Vec<u8>,
+    battery: bool,
+
+    rom_banks: usize,
+
+    ram_enabled: bool,
+    ROMB: u8,
+}
+
+impl Mbc2 {
+    pub fn new(rom: Vec<u8>, battery: bool) -> Mbc2 {
+        Mbc2 {
+            rom_banks: rom.len() / ROM_BANK_SIZE,
+            rom,
+            ram: vec![0; RAM_SIZE],
+            battery,
+
+            ram_enabled: false,
+            ROMB: 0x01,
+        }
+    }
+
+    fn ram_address(addr: u16) -> usize {
+        (addr as usize) & (RAM_SIZE - 1)
+    }
+}
+
+impl Mapper for Mbc2 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        if addr < 0x4000 {
+            return self.rom[addr as usize];
+        }
+        let bank = self.ROMB as usize % self.rom_banks.max(1);
+        self.rom[bank * ROM_BANK_SIZE + (addr as usize - 0x4000)]
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        self.ram[Self::ram_address(addr)] | 0xF0
+    }
+
+    fn write_ram(&mut self, addr: u16, data: u8) {
+        if self.ram_enabled {
+            self.ram[Self::ram_address(addr)] = data & 0x0F;
+        }
+    }
+
+    //Only 0x0000 - 0x3FFF does anything, 0x4000 - 0x7FFF isn't decoded.
+    fn write_control(&mut self, addr: u16, data: u8) {
+        if addr >= 0x4000 {
+            return;
+        }
+
+        if (addr & 0x0100) == 0 {
+            self.ram_enabled = data & 0x0F == 0x0A;
+        }
+        else {
+            self.ROMB = data & 0x0F;
+            if self.ROMB == 0 {
+                self.ROMB = 1;
+            }
+        }
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram, data);
+        for nibble in &mut self.ram {
+            *nibble &= 0x0F;
+        }
+    }
+}
@@ -0,0 +1,426 @@
+/*
+    MBC3, up to 2MiB rom, 32KiB RAM and optional real time clock. https://gbdev.io/pandocs/MBC3.html
+
+    0x0000 - 0x1FFF - RAMG, 0x0A in lower nibble enables RAM and clock registers.
+    0x2000 - 0x3FFF - ROMB, 7 bits, 0 is turned into 1.
+    0x4000 - 0x5FFF - RAMB, 0x00 - 0x03 picks RAM bank, 0x08 - 0x0C maps clock register to 0xA000 - 0xBFFF.
+    0x6000 - 0x7FFF - latch, writing 0x00 then 0x01 copies running clock into registers the cpu reads.
+
+    Clock registers:
+        0x08 - S, seconds 0 - 59
+        0x09 - M, minutes 0 - 59
+        0x0A - H, hours 0 - 23
+        0x0B - DL, lower 8 bits of day counter
+        0x0C - DH, bit 0: day counter bit 8, bit 6: halt, bit 7: day counter carry
+
+    Clock runs off its own 32768Hz crystal, it can either follow emulated cycles or host time. Emulated cycles
+    are deterministic and stop when emulator does, host time keeps going even while emulator isn't running.
+*/
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::gameboy::CPU_FREQUENCY;
+
+use super::{restore_ram, Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+const RTC_SECONDS: u8 = 0x08;
+const RTC_DAYS_HIGH: u8 = 0x0C;
+
+//Writable bits of S, M, H, DL and DH, rest always read 0.
+const RTC_MASKS: [u8; 5] = [0x3F, 0x3F, 0x1F, 0xFF, 0xC1];
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+//Day counter is 9 bits, carry gets set when it wraps.
+const DAY_COUNTER_LIMIT: u64 = 512;
+
+//Clock part of save file as most emulators write it: S, M, H, DL, DH running then latched, each as 32 bit
+//little endian, followed by unix timestamp of when it was saved.
+const RTC_SAVE_SIZE: usize = 48;
+//Older saves have 32 bit timestamp.
+const RTC_SAVE_SIZE_SHORT: usize = 44;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RtcClock {
+    #[default]
+    Emulated,
+    Host,
+}
+
+#[derive(Clone, Copy, Default)]
+struct RtcRegisters {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halt: bool,
+    carry: bool,
+}
+
+impl RtcRegisters {
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => self.days as u8,
+            _ => (self.days >> 8) as u8 | (self.halt as u8) << 6 | (self.carry as u8) << 7,
+        }
+    }
+
+    fn write(&mut self, register: u8, data: u8) {
+        let data = data & RTC_MASKS[(register - RTC_SECONDS) as usize];
+        match register {
+            0x08 => {
+                self.seconds = data;
+            }
+
+            0x09 => {
+                self.minutes = data;
+            }
+
+            0x0A => {
+                self.hours = data;
+            }
+
+            0x0B => {
+                self.days = (self.days & 0x100) | data as u16;
+            }
+
+            _ => {
+                self.days = (self.days & 0xFF) | ((data & 0x01) as u16) << 8;
+                self.halt = data & 0x40 != 0;
+                self.carry = data & 0x80 != 0;
+            }
+        }
+    }
+
+    /*
+        Counters only roll over when they hit their limit exactly. Out of range values written by software keep
+        counting up until register bits overflow, without carrying into next counter.
+    */
+    fn tick_second(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+
+        self.days += 1;
+        if self.days as u64 == DAY_COUNTER_LIMIT {
+            self.days = 0;
+            self.carry = true;
+        }
+    }
+
+    fn in_range(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    /*
+        Out of range registers get ticked one by one until they wrap back into range, that takes at most
+        a few hours worth of seconds. Rest is counted in one go, so catching up on years takes no time.
+    */
+    fn advance(&mut self, mut seconds: u64) {
+        while seconds > 0 && !self.in_range() {
+            self.tick_second();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        let total = self.days as u64 * SECONDS_PER_DAY
+            + self.hours as u64 * 3600
+            + self.minutes as u64 * 60
+            + self.seconds as u64
+            + seconds;
+
+        let days = total / SECONDS_PER_DAY;
+        if days >= DAY_COUNTER_LIMIT {
+            self.carry = true;
+        }
+        self.days = (days % DAY_COUNTER_LIMIT) as u16;
+
+        let time_of_day = total % SECONDS_PER_DAY;
+        self.hours = (time_of_day / 3600) as u8;
+        self.minutes = (time_of_day / 60 % 60) as u8;
+        self.seconds = (time_of_day % 60) as u8;
+    }
+
+    fn save(&self, data: &mut Vec<u8>) {
+        for register in RTC_SECONDS..=RTC_DAYS_HIGH {
+            data.extend((self.read(register) as u32).to_le_bytes());
+        }
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        for (register, value) in (RTC_SECONDS..=RTC_DAYS_HIGH).zip(data.chunks_exact(4)) {
+            self.write(register, value[0]);
+        }
+    }
+}
+
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    battery: bool,
+    has_rtc: bool,
+
+    rom_banks: usize,
+    ram_banks: usize,
+
+    ram_enabled: bool,
+    ROMB: u8,
+    RAMB: u8,
+    last_latch_write: u8,
+
+    //Running clock and the copy cpu sees, updated on latch.
+    rtc: RtcRegisters,
+    latched: RtcRegisters,
+
+    clock: RtcClock,
+    //Progress towards next second for emulated clock.
+    subsecond_cycles: u32,
+    //Point in host time `rtc` is accurate for, whole seconds since then haven't been counted yet.
+    last_sync: SystemTime,
+}
+
+impl Mbc3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, battery: bool, has_rtc: bool, clock: RtcClock) -> Mbc3 {
+        Mbc3 {
+            rom_banks: rom.len() / ROM_BANK_SIZE,
+            ram_banks: (ram_size / RAM_BANK_SIZE).max(1),
+            rom,
+            ram: vec![0; ram_size],
+            battery,
+            has_rtc,
+
+            ram_enabled: false,
+            ROMB: 0x01,
+            RAMB: 0x00,
+            last_latch_write: 0xFF,
+
+            rtc: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+
+            clock,
+            subsecond_cycles: 0,
+            last_sync: SystemTime::now(),
+        }
+    }
+
+    fn ram_address(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() || self.RAMB > 0x03 {
+            return None;
+        }
+
+        let bank = self.RAMB as usize & (self.ram_banks - 1);
+        Some((bank * RAM_BANK_SIZE + (addr - 0xA000) as usize) % self.ram.len())
+    }
+
+    fn rtc_selected(&self) -> bool {
+        self.ram_enabled && self.has_rtc && (RTC_SECONDS..=RTC_DAYS_HIGH).contains(&self.RAMB)
+    }
+
+    //Counts host seconds passed since last sync. Emulated clock is kept up to date by tick() instead.
+    fn sync_host_time(&mut self) {
+        if self.clock != RtcClock::Host {
+            return;
+        }
+
+        let now = SystemTime::now();
+        if self.rtc.halt {
+            self.last_sync = now;
+            return;
+        }
+
+        let seconds = now.duration_since(self.last_sync).unwrap_or_default().as_secs();
+        self.rtc.advance(seconds);
+        self.last_sync += Duration::from_secs(seconds);
+    }
+
+    fn write_rtc(&mut self, data: u8) {
+        self.sync_host_time();
+        self.rtc.write(self.RAMB, data);
+        self.latched.write(self.RAMB, data);
+
+        //Writing seconds restarts the crystal divider, next second is a whole second away.
+        if self.RAMB == RTC_SECONDS {
+            self.subsecond_cycles = 0;
+            self.last_sync = SystemTime::now();
+        }
+    }
+
+    fn restore_rtc(&mut self, data: &[u8]) {
+        self.rtc.restore(&data[0..20]);
+        self.latched.restore(&data[20..40]);
+
+        let timestamp = if data.len() >= RTC_SAVE_SIZE {
+            u64::from_le_bytes(data[40..48].try_into().unwrap())
+        }
+        else {
+            u32::from_le_bytes(data[40..44].try_into().unwrap()) as u64
+        };
+
+        //Host clock catches up on time that passed while game wasn't running.
+        let saved_at = UNIX_EPOCH + Duration::from_secs(timestamp);
+        self.last_sync = saved_at.min(SystemTime::now());
+        self.subsecond_cycles = 0;
+        self.sync_host_time();
+    }
+}
+
+impl Mapper for Mbc3 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        if addr < 0x4000 {
+            return self.rom[addr as usize];
+        }
+        let bank = self.ROMB as usize % self.rom_banks.max(1);
+        self.rom[bank * ROM_BANK_SIZE + (addr as usize - 0x4000)]
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if self.rtc_selected() {
+            return self.latched.read(self.RAMB);
+        }
+
+        match self.ram_address(addr) {
+            Some(address) => self.ram[address],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, data: u8) {
+        if self.rtc_selected() {
+            self.write_rtc(data);
+        }
+        else if let Some(address) = self.ram_address(addr) {
+            self.ram[address] = data;
+        }
+    }
+
+    fn write_control(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = data & 0x0F == 0x0A;
+            }
+
+            0x2000..=0x3FFF => {
+                self.ROMB = data & 0x7F;
+                if self.ROMB == 0 {
+                    self.ROMB = 1;
+                }
+            }
+
+            0x4000..=0x5FFF => {
+                self.RAMB = data & 0x0F;
+            }
+
+            _ => {
+                if self.last_latch_write == 0x00 && data == 0x01 {
+                    self.sync_host_time();
+                    self.latched = self.rtc;
+                }
+                self.last_latch_write = data;
+            }
+        }
+    }
+
+    //RAM followed by clock state, clock part only when cartridge has one.
+    fn save(&self) -> Option<Vec<u8>> {
+        if !self.battery || (self.ram.is_empty() && !self.has_rtc) {
+            return None;
+        }
+
+        let mut data = self.ram.clone();
+        if self.has_rtc {
+            self.rtc.save(&mut data);
+            self.latched.save(&mut data);
+
+            //Host clock hasn't counted seconds since last sync yet, timestamp says from when to continue.
+            let saved_at = match self.clock {
+                RtcClock::Host => self.last_sync,
+                RtcClock::Emulated => SystemTime::now(),
+            };
+            let timestamp = saved_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            data.extend(timestamp.to_le_bytes());
+        }
+        Some(data)
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram, data);
+
+        let rtc_data = &data[self.ram.len().min(data.len())..];
+        if self.has_rtc && rtc_data.len() >= RTC_SAVE_SIZE_SHORT {
+            self.restore_rtc(rtc_data);
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if !self.has_rtc || self.clock != RtcClock::Emulated || self.rtc.halt {
+            return;
+        }
+
+        self.subsecond_cycles += cycles;
+        while self.subsecond_cycles >= CPU_FREQUENCY {
+            self.subsecond_cycles -= CPU_FREQUENCY;
+            self.rtc.tick_second();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers(rtc: &RtcRegisters) -> [u8; 5] {
+        [0x08, 0x09, 0x0A, 0x0B, 0x0C].map(|register| rtc.read(register))
+    }
+
+    #[test]
+    fn advance_multi_year_gap_wraps_day_counter() {
+        let mut rtc = RtcRegisters::default();
+
+        //3 years and 5 days, 1100 days in total. 1100 % 512 = 76.
+        rtc.advance((3 * 365 + 5) * SECONDS_PER_DAY + 3600 + 2 * 60 + 3);
+        assert_eq!(registers(&rtc), [3, 2, 1, 76, 0x80]);
+    }
+
+    #[test]
+    fn advance_from_last_day_sets_carry() {
+        let mut rtc = RtcRegisters { seconds: 59, minutes: 59, hours: 23, days: 511, ..Default::default() };
+        rtc.advance(1);
+        assert_eq!(registers(&rtc), [0, 0, 0, 0, 0x80]);
+
+        //Day 300 is DL 44 with day bit 8 set, carry stays.
+        rtc.advance(300 * SECONDS_PER_DAY);
+        assert_eq!(registers(&rtc), [0, 0, 0, 44, 0x81]);
+    }
+
+    #[test]
+    fn advance_matches_ticking_every_second() {
+        //Out of range seconds have to wrap through register width first.
+        let start = RtcRegisters { seconds: 62, minutes: 59, hours: 23, days: 300, ..Default::default() };
+        let mut ticked = start;
+        let mut advanced = start;
+
+        for _ in 0..(250 * SECONDS_PER_DAY) {
+            ticked.tick_second();
+        }
+        advanced.advance(250 * SECONDS_PER_DAY);
+        assert_eq!(registers(&advanced), registers(&ticked));
+    }
+}
@@ -0,0 +1,117 @@
+/*
+    MBC5, up to 8MiB rom and 128KiB RAM, optionally with rumble motor. https://gbdev.io/pandocs/MBC5.html
+
+    0x0000 - 0x1FFF - RAMG, exactly 0x0A enables RAM, unlike older mappers all 8 bits are checked.
+    0x2000 - 0x2FFF - ROMB0, lower 8 rom bank bits.
+    0x3000 - 0x3FFF - ROMB1, rom bank bit 8.
+    0x4000 - 0x5FFF - RAMB, 4 bit RAM bank. On rumble cartridges bit 3 drives the motor instead.
+
+    No bank 0 quirk, any of 512 banks including bank 0 can be mapped to 0x4000 - 0x7FFF.
+*/
+
+use super::{restore_ram, Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+const RUMBLE_BIT: u8 = 0x08;
+
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    battery: bool,
+    has_rumble: bool,
+
+    rom_banks: usize,
+    ram_banks: usize,
+
+    ram_enabled: bool,
+    ROMB0: u8,
+    ROMB1: u8,
+    RAMB: u8,
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, battery: bool, has_rumble: bool) -> Mbc5 {
+        Mbc5 {
+            rom_banks: rom.len() / ROM_BANK_SIZE,
+            ram_banks: (ram_size / RAM_BANK_SIZE).max(1),
+            rom,
+            ram: vec![0; ram_size],
+            battery,
+            has_rumble,
+
+            ram_enabled: false,
+            ROMB0: 0x01,
+            ROMB1: 0x00,
+            RAMB: 0x00,
+        }
+    }
+
+    fn ram_address(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+
+        let mut bank = self.RAMB;
+        if self.has_rumble {
+            bank &= !RUMBLE_BIT;
+        }
+        let bank = bank as usize & (self.ram_banks - 1);
+        Some((bank * RAM_BANK_SIZE + (addr - 0xA000) as usize) % self.ram.len())
+    }
+}
+
+impl Mapper for Mbc5 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        if addr < 0x4000 {
+            return self.rom[addr as usize];
+        }
+        let bank = ((self.ROMB1 as usize) << 8 | self.ROMB0 as usize) % self.rom_banks.max(1);
+        self.rom[bank * ROM_BANK_SIZE + (addr as usize - 0x4000)]
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        match self.ram_address(addr) {
+            Some(address) => self.ram[address],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, data: u8) {
+        if let Some(address) = self.ram_address(addr) {
+            self.ram[address] = data;
+        }
+    }
+
+    fn write_control(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = data == 0x0A;
+            }
+
+            0x2000..=0x2FFF => {
+                self.ROMB0 = data;
+            }
+
+            0x3000..=0x3FFF => {
+                self.ROMB1 = data & 0x01;
+            }
+
+            0x4000..=0x5FFF => {
+                self.RAMB = data & 0x0F;
+            }
+
+            _ => {}
+        }
+    }
+
+    fn save(&self) -> Option<Vec<u8>> {
+        (self.battery && !self.ram.is_empty()).then(|| self.ram.clone())
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram, data);
+    }
+
+    fn rumble(&self) -> bool {
+        self.has_rumble && self.RAMB & RUMBLE_BIT != 0
+    }
+}
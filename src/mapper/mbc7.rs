@@ -0,0 +1,359 @@
+/*
+    MBC7, up to 2MiB rom with 2 axis accelerometer and 93LC56 serial EEPROM instead of RAM.
+    https://gbdev.io/pandocs/MBC7.html
+
+    0x0000 - 0x1FFF - RAMG1, 0x0A enables 0xA000 - 0xAFFF...
+    0x2000 - 0x3FFF - ROMB, 7 bits, bank 0 can be mapped too.
+    0x4000 - 0x5FFF - RAMG2, ...but only together with 0x40 written here.
+
+    0xA000 - 0xAFFF - registers, repeated every 0x100, address bits 4 - 7 pick which one:
+        Ax0x - write 0x55 erases latched accelerometer values.
+        Ax1x - write 0xAA after erase latches current accelerometer values.
+        Ax2x - Ax5x - latched X low, X high, Y low, Y high.
+        Ax6x - always 0x00.
+        Ax8x - EEPROM pins, bit 7: CS, bit 6: CLK, bit 1: DI, bit 0: DO.
+        Anything else reads 0xFF, as does 0xB000 - 0xBFFF.
+
+    Accelerometer reads 0x81D0 while level and moves about 0x70 per 1g of tilt.
+*/
+
+use super::{restore_ram, Mapper, ROM_BANK_SIZE};
+
+const ACCELEROMETER_LEVEL: f32 = 0x81D0 as f32;
+const ACCELEROMETER_PER_G: f32 = 0x70 as f32;
+//What erased latch reads until new values get latched.
+const ACCELEROMETER_ERASED: u16 = 0x8000;
+
+const EEPROM_SIZE: usize = 256;
+const EEPROM_WORDS: u8 = (EEPROM_SIZE / 2) as u8;
+
+//Start bit is followed by 2 bit opcode and 8 address bits, 93LC56 only uses lower 7 of them.
+const EEPROM_COMMAND_BITS: u8 = 10;
+
+const EEPROM_CS: u8 = 0x80;
+const EEPROM_CLK: u8 = 0x40;
+const EEPROM_DI: u8 = 0x02;
+const EEPROM_DO: u8 = 0x01;
+
+enum EepromState {
+    //Waiting for start bit, leading zeros are ignored.
+    Idle,
+    Command { value: u16, bits: u8 },
+    //Shifting out `word` MSB first, reading keeps going into next word once it's done.
+    Read { address: u8, word: u16, bits: u8 },
+    //Shifting in 16 bits of data. No address means WRAL, every word gets written.
+    Write { address: Option<u8>, value: u16, bits: u8 },
+}
+
+/*
+    93LC56 in 16 bit mode, 128 words. Bits get clocked in and out on CLK rising edge while CS is high,
+    dropping CS aborts whatever command was going on. Writes finish instantly, DO is ready (1) right away.
+*/
+struct Eeprom {
+    //Words stored little endian.
+    data: Vec<u8>,
+    write_enabled: bool,
+
+    cs: bool,
+    clk: bool,
+    di: bool,
+    do_bit: bool,
+
+    state: EepromState,
+}
+
+impl Eeprom {
+    fn new() -> Eeprom {
+        Eeprom {
+            data: vec![0xFF; EEPROM_SIZE],
+            write_enabled: false,
+
+            cs: false,
+            clk: false,
+            di: false,
+            do_bit: true,
+
+            state: EepromState::Idle,
+        }
+    }
+
+    fn word(&self, address: u8) -> u16 {
+        let index = (address % EEPROM_WORDS) as usize * 2;
+        u16::from_le_bytes([self.data[index], self.data[index + 1]])
+    }
+
+    fn set_word(&mut self, address: u8, value: u16) {
+        if self.write_enabled {
+            let index = (address % EEPROM_WORDS) as usize * 2;
+            self.data[index..index + 2].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn read_pins(&self) -> u8 {
+        let mut pins = 0;
+        if self.cs {
+            pins |= EEPROM_CS;
+        }
+        if self.clk {
+            pins |= EEPROM_CLK;
+        }
+        if self.di {
+            pins |= EEPROM_DI;
+        }
+        if self.do_bit {
+            pins |= EEPROM_DO;
+        }
+        pins
+    }
+
+    fn write_pins(&mut self, data: u8) {
+        let cs = data & EEPROM_CS != 0;
+        let clk = data & EEPROM_CLK != 0;
+        self.di = data & EEPROM_DI != 0;
+
+        if !cs {
+            self.state = EepromState::Idle;
+        }
+        else if clk && !self.clk {
+            self.clock_bit();
+        }
+
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn clock_bit(&mut self) {
+        let di = self.di as u16;
+        match self.state {
+            EepromState::Idle => {
+                if di == 1 {
+                    self.state = EepromState::Command { value: 0, bits: 0 };
+                }
+            }
+
+            EepromState::Command { value, bits } => {
+                let value = value << 1 | di;
+                if bits + 1 == EEPROM_COMMAND_BITS {
+                    self.run_command(value);
+                }
+                else {
+                    self.state = EepromState::Command { value, bits: bits + 1 };
+                }
+            }
+
+            EepromState::Read { address, word, bits } => {
+                self.do_bit = word & 0x8000 != 0;
+                if bits == 1 {
+                    let address = address.wrapping_add(1) % EEPROM_WORDS;
+                    self.state = EepromState::Read { address, word: self.word(address), bits: 16 };
+                }
+                else {
+                    self.state = EepromState::Read { address, word: word << 1, bits: bits - 1 };
+                }
+            }
+
+            EepromState::Write { address, value, bits } => {
+                let value = value << 1 | di;
+                if bits + 1 < 16 {
+                    self.state = EepromState::Write { address, value, bits: bits + 1 };
+                    return;
+                }
+
+                match address {
+                    Some(address) => self.set_word(address, value),
+                    None => {
+                        for address in 0..EEPROM_WORDS {
+                            self.set_word(address, value);
+                        }
+                    }
+                }
+                self.do_bit = true;
+                self.state = EepromState::Idle;
+            }
+        }
+    }
+
+    fn run_command(&mut self, command: u16) {
+        let opcode = command >> 8;
+        let address = (command & 0x7F) as u8;
+        self.state = EepromState::Idle;
+
+        match opcode {
+            //READ, dummy 0 comes out first.
+            0b10 => {
+                self.do_bit = false;
+                self.state = EepromState::Read { address, word: self.word(address), bits: 16 };
+            }
+
+            //WRITE
+            0b01 => {
+                self.state = EepromState::Write { address: Some(address), value: 0, bits: 0 };
+            }
+
+            //ERASE
+            0b11 => {
+                self.set_word(address, 0xFFFF);
+                self.do_bit = true;
+            }
+
+            //Upper 2 address bits pick one of extended commands.
+            _ => {
+                match (command >> 6) & 0x03 {
+                    //EWDS
+                    0b00 => {
+                        self.write_enabled = false;
+                    }
+
+                    //WRAL
+                    0b01 => {
+                        self.state = EepromState::Write { address: None, value: 0, bits: 0 };
+                    }
+
+                    //ERAL
+                    0b10 => {
+                        for address in 0..EEPROM_WORDS {
+                            self.set_word(address, 0xFFFF);
+                        }
+                        self.do_bit = true;
+                    }
+
+                    //EWEN
+                    _ => {
+                        self.write_enabled = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct Mbc7 {
+    rom: Vec<u8>,
+    rom_banks: usize,
+
+    RAMG1: bool,
+    RAMG2: bool,
+    ROMB: u8,
+
+    //Tilt from frontend in g, positive is right and down.
+    tilt_x: f32,
+    tilt_y: f32,
+    latch_erased: bool,
+    latched_x: u16,
+    latched_y: u16,
+
+    eeprom: Eeprom,
+}
+
+impl Mbc7 {
+    pub fn new(rom: Vec<u8>) -> Mbc7 {
+        Mbc7 {
+            rom_banks: rom.len() / ROM_BANK_SIZE,
+            rom,
+
+            RAMG1: false,
+            RAMG2: false,
+            ROMB: 0x01,
+
+            tilt_x: 0.0,
+            tilt_y: 0.0,
+            latch_erased: false,
+            latched_x: ACCELEROMETER_ERASED,
+            latched_y: ACCELEROMETER_ERASED,
+
+            eeprom: Eeprom::new(),
+        }
+    }
+
+    fn registers_enabled(&self, addr: u16) -> bool {
+        self.RAMG1 && self.RAMG2 && addr < 0xB000
+    }
+
+    fn accelerometer_value(tilt: f32) -> u16 {
+        (ACCELEROMETER_LEVEL + tilt * ACCELEROMETER_PER_G) as u16
+    }
+}
+
+impl Mapper for Mbc7 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        if addr < 0x4000 {
+            return self.rom[addr as usize];
+        }
+        let bank = self.ROMB as usize % self.rom_banks.max(1);
+        self.rom[bank * ROM_BANK_SIZE + (addr as usize - 0x4000)]
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.registers_enabled(addr) {
+            return 0xFF;
+        }
+
+        match (addr >> 4) & 0x0F {
+            0x2 => self.latched_x as u8,
+            0x3 => (self.latched_x >> 8) as u8,
+            0x4 => self.latched_y as u8,
+            0x5 => (self.latched_y >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read_pins(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, data: u8) {
+        if !self.registers_enabled(addr) {
+            return;
+        }
+
+        match (addr >> 4) & 0x0F {
+            0x0 if data == 0x55 => {
+                self.latch_erased = true;
+                self.latched_x = ACCELEROMETER_ERASED;
+                self.latched_y = ACCELEROMETER_ERASED;
+            }
+
+            0x1 if data == 0xAA && self.latch_erased => {
+                self.latch_erased = false;
+                self.latched_x = Self::accelerometer_value(self.tilt_x);
+                self.latched_y = Self::accelerometer_value(self.tilt_y);
+            }
+
+            0x8 => {
+                self.eeprom.write_pins(data);
+            }
+
+            _ => {}
+        }
+    }
+
+    fn write_control(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.RAMG1 = data == 0x0A;
+            }
+
+            0x2000..=0x3FFF => {
+                self.ROMB = data & 0x7F;
+            }
+
+            0x4000..=0x5FFF => {
+                self.RAMG2 = data == 0x40;
+            }
+
+            _ => {}
+        }
+    }
+
+    //EEPROM keeps its contents without battery, it gets saved just like battery RAM.
+    fn save(&self) -> Option<Vec<u8>> {
+        Some(self.eeprom.data.clone())
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        restore_ram(&mut self.eeprom.data, data);
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt_x = x;
+        self.tilt_y = y;
+    }
+}
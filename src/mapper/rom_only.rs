@@ -0,0 +1,47 @@
+/*
+    32KiB rom mapped straight in, optionally with up to 8KiB of RAM. Writes to rom area do nothing.
+*/
+
+use super::{restore_ram, Mapper};
+
+pub struct RomOnly {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    battery: bool,
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>, ram_size: usize, battery: bool) -> RomOnly {
+        RomOnly {
+            rom,
+            ram: vec![0; ram_size],
+            battery,
+        }
+    }
+}
+
+impl Mapper for RomOnly {
+    fn read_rom(&self, addr: u16) -> u8 {
+        self.rom.get(addr as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        self.ram.get((addr - 0xA000) as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, addr: u16, data: u8) {
+        if let Some(byte) = self.ram.get_mut((addr - 0xA000) as usize) {
+            *byte = data;
+        }
+    }
+
+    fn write_control(&mut self, _addr: u16, _data: u8) {}
+
+    fn save(&self) -> Option<Vec<u8>> {
+        (self.battery && !self.ram.is_empty()).then(|| self.ram.clone())
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram, data);
+    }
+}
@@ -0,0 +1,100 @@
+/*
+    Keeps frontend running at real DMG refresh rate, CYCLES_PER_FRAME / CPU_FREQUENCY seconds per frame (~59.73Hz).
+    Deadlines accumulate from fixed start point, so rounding of individual sleeps doesn't drift over time.
+
+    Speed multiplier scales frame duration (2.0 = twice as many frames per second), unthrottled skips waiting entirely.
+*/
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::gameboy::{CPU_FREQUENCY, CYCLES_PER_FRAME};
+
+//OS sleep can overshoot, last bit of waiting is done by spinning.
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+//If we fall this far behind (slow host, window dragged, debugger) don't try to catch up, just start over.
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 8.0;
+
+pub fn frame_duration() -> Duration {
+    Duration::from_nanos(CYCLES_PER_FRAME as u64 * 1_000_000_000 / CPU_FREQUENCY as u64)
+}
+
+pub struct FramePacer {
+    frame_duration: Duration,
+    next_frame: Instant,
+
+    speed: f64,
+    unthrottled: bool,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FramePacer {
+    pub fn new() -> FramePacer {
+        FramePacer {
+            frame_duration: frame_duration(),
+            next_frame: Instant::now(),
+
+            speed: 1.0,
+            unthrottled: false,
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    //Clamped to MIN_SPEED - MAX_SPEED.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        self.frame_duration = frame_duration().div_f64(self.speed);
+        self.reset();
+    }
+
+    pub fn is_unthrottled(&self) -> bool {
+        self.unthrottled
+    }
+
+    pub fn set_unthrottled(&mut self, unthrottled: bool) {
+        self.unthrottled = unthrottled;
+        self.reset();
+    }
+
+    //Start counting from now, e.g. after emulation was paused.
+    pub fn reset(&mut self) {
+        self.next_frame = Instant::now();
+    }
+
+    //Blocks until it's time for next frame.
+    pub fn wait(&mut self) {
+        if self.unthrottled {
+            return;
+        }
+
+        self.next_frame += self.frame_duration;
+
+        let now = Instant::now();
+        if now >= self.next_frame {
+            if now - self.next_frame > MAX_LAG {
+                self.next_frame = now;
+            }
+            return;
+        }
+
+        let remaining = self.next_frame - now;
+        if remaining > SPIN_MARGIN {
+            thread::sleep(remaining - SPIN_MARGIN);
+        }
+        while Instant::now() < self.next_frame {
+            std::hint::spin_loop();
+        }
+    }
+}
@@ -1,38 +1,41 @@
 use std::{cell::RefCell, rc::Rc};
 
-use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window};
+use crate::{databus::DataBus, interrupts::Interrupt, SCREEN_WIDTH, SCREEN_HEIGHT};
 
-use crate::{databus::DataBus, SCREEN_SCALE};
-
-pub struct Ppu <'a> {
-    colors: [Color; 4],
+pub const FRAMEBUFFER_SIZE: usize = (SCREEN_WIDTH * SCREEN_HEIGHT) as usize;
 
+pub struct Ppu {
     current_line_cycles: u16,
 
-    canvas: &'a mut Canvas<Window>,
+    /*
+        One byte per pixel, row by row.
+        Holds colour id 0..=3 (0 = lightest, 3 = darkest), frontend decides actual colours.
+    */
+    framebuffer: [u8; FRAMEBUFFER_SIZE],
 
     databus: Rc<RefCell<DataBus>>,
 }
 
-impl<'a> Ppu<'a> {
-    pub fn new(databus: Rc<RefCell<DataBus>>, canvas: &mut Canvas<Window>) -> Ppu {
+impl Ppu {
+    pub fn new(databus: Rc<RefCell<DataBus>>) -> Ppu {
         Ppu{
-            //https://pixelcod.itch.io/ghost
-            colors: [
-                     Color::RGB(0xFC, 0xEE, 0xE8),
-                     Color::RGB(0xDC, 0x95, 0xA7),
-                     Color::RGB(0x71, 0x5A, 0x9B),
-                     Color::RGB(0x10, 0x10, 0x18),
-            ],
-
             current_line_cycles: 456,
 
-            canvas,
+            framebuffer: [0; FRAMEBUFFER_SIZE],
 
             databus,
         }
     }
 
+    pub fn framebuffer(&self) -> &[u8; FRAMEBUFFER_SIZE] {
+        &self.framebuffer
+    }
+
+    //Turned off screen shows lightest colour.
+    pub fn blank(&mut self) {
+        self.framebuffer = [0; FRAMEBUFFER_SIZE];
+    }
+
     fn updated_STAT(&mut self) {
         let STAT: u8 = self.databus.borrow().read_memory(0xFF41);
         let LCDC: u8 = self.databus.borrow().read_memory(0xFF40);
@@ -75,17 +78,13 @@ impl<'a> Ppu<'a> {
         }
 
         if reqint && (current_mode != new_mode) {
-                let IF = self.databus.borrow().read_memory(0xFF0F);
-                let updated_IF = IF | 1;
-                self.databus.borrow_mut().write_memory(updated_IF, 0xFF0F);
+            self.databus.borrow_mut().interrupts.request(Interrupt::LcdStat);
         }
 
         if LY == LYC {
             updated_STAT |= 1 << 2;
             if ((STAT >> 6) & 0x01) == 1 {
-                let IF = self.databus.borrow().read_memory(0xFF0F);
-                let updated_IF = IF | 1;
-                self.databus.borrow_mut().write_memory(updated_IF, 0xFF0F);
+                self.databus.borrow_mut().interrupts.request(Interrupt::LcdStat);
             }
         }
         else {
@@ -147,7 +146,7 @@ impl<'a> Ppu<'a> {
         };
 
         for pixel in 0..160 {
-            let mut tile_col = ((SCX.wrapping_add(pixel)) as u16 / 8);
+            let mut tile_col = (SCX.wrapping_add(pixel)) as u16 / 8;
 
             if using_window && (pixel >= WX) {
                 tile_col = ((pixel - WX) as u16 / 8) & 0x1f;
@@ -156,20 +155,15 @@ impl<'a> Ppu<'a> {
             let tile_address: u16 = background_map + tile_row + tile_col;
             let tile_num = databus_borrow.read_memory(tile_address);
 
-            let mut tile_location: u16 = 0;
-
-            if unsig {
-                tile_location = tile_data + (tile_num as u16 * 16);
+            let tile_location: u16 = if unsig {
+                tile_data + (tile_num as u16 * 16)
             }
-            else {
-                if (tile_num >= 0) && (tile_num < 128){
-                    tile_location = 0x9000 + (tile_num as u16 * 16);
-                }
-                else {
-                    tile_location = 0x8800 + (((tile_num as u16) - 128) * 16);
-                }
-                //tile_location = tile_data + ((tile_num as u16).wrapping_add(128).wrapping_mul(16));
+            else if tile_num < 128 {
+                0x9000 + (tile_num as u16 * 16)
             }
+            else {
+                0x8800 + (((tile_num as u16) - 128) * 16)
+            };
 
             let line = ((SCY as u16 + LY as u16) % 8) * 2;
             let data1 = databus_borrow.read_memory(tile_location + line);
@@ -182,8 +176,7 @@ impl<'a> Ppu<'a> {
             let data1_bit = (data1 >> colour_bit) & 0x01;
             let data2_bit = (data2 >> colour_bit) & 0x01;
             let final_colour = (data2_bit << 1) | data1_bit;
-            self.canvas.set_draw_color(self.colors[final_colour as usize]);
-            let _ = self.canvas.fill_rect(Rect::new(pixel as i32 * SCREEN_SCALE as i32,LY as i32 * SCREEN_SCALE as i32 , SCREEN_SCALE, SCREEN_SCALE));
+            self.framebuffer[LY as usize * SCREEN_WIDTH as usize + pixel as usize] = final_colour;
         }
     }
 
@@ -243,8 +236,12 @@ impl<'a> Ppu<'a> {
 
                     let xPix = (7 - pixel as u8).wrapping_add(xPos);
 
-                    self.canvas.set_draw_color(self.colors[final_colour as usize]);
-                    let _ = self.canvas.fill_rect(Rect::new(xPix as i32 * SCREEN_SCALE as i32,LY as i32 * SCREEN_SCALE as i32 , SCREEN_SCALE, SCREEN_SCALE));
+                    //Sprite partially off screen.
+                    if xPix as u32 >= SCREEN_WIDTH {
+                        continue;
+                    }
+
+                    self.framebuffer[LY as usize * SCREEN_WIDTH as usize + xPix as usize] = final_colour;
                 }
             }
         }
@@ -281,10 +278,7 @@ impl<'a> Ppu<'a> {
             }
 
             else if LY == 144 {
-                //Request interrup.
-                let IF = self.databus.borrow().read_memory(0xFF0F);
-                let updated_IF = IF | 1;
-                self.databus.borrow_mut().write_memory(updated_IF, 0xFF0F);
+                self.databus.borrow_mut().interrupts.request(Interrupt::VBlank);
             }
 
             else if LY > 153 {
@@ -298,8 +292,4 @@ impl<'a> Ppu<'a> {
             self.databus.borrow_mut().write_memory(LY + 1, 0xFF44);
         }
     }
-
-    pub fn present(&mut self) {
-        self.canvas.present();
-    }
 }
@@ -0,0 +1,396 @@
+/*
+    Game Boy Printer on the other end of link cable. Game is always master, printer answers every byte it gets.
+    https://gbdev.io/pandocs/Gameboy_Printer.html
+
+    Packet sent by game:
+        0x88 0x33           - magic
+        command             - INIT / PRINT / DATA / BREAK / STATUS
+        compression         - 1 if data is RLE compressed
+        length              - 2 bytes, little endian
+        data                - `length` bytes
+        checksum            - 2 bytes, little endian, sum of command through data
+        0x00 0x00           - printer answers 0x81 (it's alive) and then its status
+
+    Printer answers 0x00 to everything before that.
+
+    Data is 2bpp tiles, 20 tiles per row (160 pixels). Each print ends up as PNG in output directory.
+    Prints with no feed after them are continued by next print, games print long images in parts that way.
+    Printer doesn't report anything itself, frontend finds out about saved prints through print callback.
+*/
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::serial::LinkPeer;
+
+const MAGIC: [u8; 2] = [0x88, 0x33];
+
+const COMMAND_INIT: u8 = 0x01;
+const COMMAND_PRINT: u8 = 0x02;
+const COMMAND_DATA: u8 = 0x04;
+const COMMAND_BREAK: u8 = 0x08;
+const COMMAND_STATUS: u8 = 0x0F;
+
+const ALIVE: u8 = 0x81;
+
+const STATUS_CHECKSUM_ERROR: u8 = 0x01;
+const STATUS_BUSY: u8 = 0x02;
+const STATUS_IMAGE_FULL: u8 = 0x04;
+const STATUS_UNPROCESSED_DATA: u8 = 0x08;
+
+//Printer has 8KiB of RAM, DATA packets are usually 640 bytes (2 tile rows each).
+const BUFFER_SIZE: usize = 0x2000;
+
+const WIDTH: usize = 160;
+const TILES_PER_ROW: usize = WIDTH / 8;
+const BYTES_PER_TILE_ROW: usize = TILES_PER_ROW * 16;
+
+//Games poll status until printer stops being busy, real printing takes a few seconds. We're done right away,
+//but stay busy for a few polls since some games expect to see busy flag at least once.
+const BUSY_STATUS_POLLS: u32 = 4;
+
+//One feed from margin byte, roughly height of a tile row on paper.
+const FEED_HEIGHT: usize = 8;
+
+//Paper colour to ink, indexed by shade from palette byte.
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PacketState {
+    Magic,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+pub struct Printer {
+    output_dir: PathBuf,
+
+    state: PacketState,
+    magic_received: usize,
+    command: u8,
+    compressed: bool,
+    length: usize,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+
+    status: u8,
+    busy_polls: u32,
+
+    //Tile data received since last INIT / PRINT.
+    buffer: Vec<u8>,
+    //Shade per pixel, WIDTH wide. Printed but not yet cut off, waiting for feed.
+    strip: Vec<u8>,
+
+    pages_printed: usize,
+    print_callback: Option<Box<dyn FnMut(io::Result<PathBuf>)>>,
+}
+
+impl Printer {
+    //Printed images go to `output_dir`, it's created on first print if needed.
+    pub fn new(output_dir: impl Into<PathBuf>) -> Printer {
+        Printer {
+            output_dir: output_dir.into(),
+
+            state: PacketState::Magic,
+            magic_received: 0,
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+
+            status: 0x00,
+            busy_polls: 0,
+
+            buffer: Vec::new(),
+            strip: Vec::new(),
+
+            pages_printed: 0,
+            print_callback: None,
+        }
+    }
+
+    //Called with path of every saved print, or error when it couldn't be saved.
+    pub fn set_print_callback(&mut self, callback: Box<dyn FnMut(io::Result<PathBuf>)>) {
+        self.print_callback = Some(callback);
+    }
+
+    pub fn pages_printed(&self) -> usize {
+        self.pages_printed
+    }
+
+    //Every byte between magic and checksum counts towards it.
+    fn checksummed(&mut self, data: u8) {
+        self.checksum = self.checksum.wrapping_add(data as u16);
+    }
+
+    fn receive(&mut self, data: u8) -> u8 {
+        match self.state {
+            PacketState::Magic => {
+                if data == MAGIC[self.magic_received] {
+                    self.magic_received += 1;
+                }
+                else {
+                    //0x88 0x88 0x33 is still fine, second 0x88 starts magic over.
+                    self.magic_received = (data == MAGIC[0]) as usize;
+                }
+                if self.magic_received == MAGIC.len() {
+                    self.magic_received = 0;
+                    self.checksum = 0;
+                    self.data.clear();
+                    self.state = PacketState::Command;
+                }
+                0x00
+            }
+
+            PacketState::Command => {
+                self.command = data;
+                self.checksummed(data);
+                self.state = PacketState::Compression;
+                0x00
+            }
+
+            PacketState::Compression => {
+                self.compressed = (data & 0x01) != 0;
+                self.checksummed(data);
+                self.state = PacketState::LengthLow;
+                0x00
+            }
+
+            PacketState::LengthLow => {
+                self.length = data as usize;
+                self.checksummed(data);
+                self.state = PacketState::LengthHigh;
+                0x00
+            }
+
+            PacketState::LengthHigh => {
+                self.length |= (data as usize) << 8;
+                self.checksummed(data);
+                self.state = if self.length == 0 { PacketState::ChecksumLow } else { PacketState::Data };
+                0x00
+            }
+
+            PacketState::Data => {
+                self.data.push(data);
+                self.checksummed(data);
+                if self.data.len() == self.length {
+                    self.state = PacketState::ChecksumLow;
+                }
+                0x00
+            }
+
+            PacketState::ChecksumLow => {
+                self.received_checksum = data as u16;
+                self.state = PacketState::ChecksumHigh;
+                0x00
+            }
+
+            PacketState::ChecksumHigh => {
+                self.received_checksum |= (data as u16) << 8;
+                self.state = PacketState::Alive;
+
+                if self.received_checksum == self.checksum {
+                    self.status &= !STATUS_CHECKSUM_ERROR;
+                    self.execute();
+                }
+                else {
+                    self.status |= STATUS_CHECKSUM_ERROR;
+                }
+                0x00
+            }
+
+            PacketState::Alive => {
+                self.state = PacketState::Status;
+                ALIVE
+            }
+
+            PacketState::Status => {
+                self.state = PacketState::Magic;
+                let status = self.status;
+                self.tick_busy();
+                status
+            }
+        }
+    }
+
+    fn tick_busy(&mut self) {
+        if self.busy_polls > 0 {
+            self.busy_polls -= 1;
+            if self.busy_polls == 0 {
+                self.status &= !(STATUS_BUSY | STATUS_IMAGE_FULL);
+            }
+        }
+    }
+
+    fn execute(&mut self) {
+        match self.command {
+            COMMAND_INIT => {
+                self.buffer.clear();
+                self.status = 0x00;
+                self.busy_polls = 0;
+            }
+
+            //Empty DATA packet just marks end of data.
+            COMMAND_DATA if !self.data.is_empty() => {
+                let data = if self.compressed { decompress(&self.data) } else { std::mem::take(&mut self.data) };
+                let free = BUFFER_SIZE - self.buffer.len();
+                self.buffer.extend_from_slice(&data[..data.len().min(free)]);
+                self.status |= STATUS_UNPROCESSED_DATA;
+            }
+
+            COMMAND_PRINT => {
+                if self.data.len() >= 4 {
+                    let (sheets, margins, palette) = (self.data[0], self.data[1], self.data[2]);
+                    self.print(sheets, margins >> 4, margins & 0x0F, palette);
+                }
+                self.status = (self.status & !STATUS_UNPROCESSED_DATA) | STATUS_BUSY | STATUS_IMAGE_FULL;
+                self.busy_polls = BUSY_STATUS_POLLS;
+            }
+
+            COMMAND_BREAK => {
+                self.buffer.clear();
+                self.status &= !(STATUS_BUSY | STATUS_IMAGE_FULL | STATUS_UNPROCESSED_DATA);
+                self.busy_polls = 0;
+            }
+
+            COMMAND_STATUS => {}
+
+            _ => {}
+        }
+    }
+
+    fn feed(&mut self, feeds: u8) {
+        self.strip.resize(self.strip.len() + feeds as usize * FEED_HEIGHT * WIDTH, SHADES[0]);
+    }
+
+    /*
+        Palette maps colour ids to shades same way BGP does, 0x00 is treated as default 0xE4 like real printer.
+        Sheets = 0 only feeds paper.
+    */
+    fn print(&mut self, sheets: u8, feeds_before: u8, feeds_after: u8, palette: u8) {
+        let palette = if palette == 0x00 { 0xE4 } else { palette };
+
+        self.feed(feeds_before);
+        for _ in 0..sheets {
+            render_tiles(&self.buffer, palette, &mut self.strip);
+        }
+        self.buffer.clear();
+        self.feed(feeds_after);
+
+        //Without feed paper stays in printer and next print continues on it.
+        if feeds_after > 0 {
+            self.cut();
+        }
+    }
+
+    fn cut(&mut self) {
+        if self.strip.is_empty() {
+            return;
+        }
+
+        let strip = std::mem::take(&mut self.strip);
+        let result = write_png(&self.output_dir, &strip);
+        if result.is_ok() {
+            self.pages_printed += 1;
+        }
+        if let Some(callback) = &mut self.print_callback {
+            callback(result);
+        }
+    }
+}
+
+//Whatever is still in printer when emulator closes.
+impl Drop for Printer {
+    fn drop(&mut self) {
+        self.cut();
+    }
+}
+
+impl LinkPeer for Printer {
+    fn exchange(&mut self, outgoing: u8) -> u8 {
+        self.receive(outgoing)
+    }
+}
+
+/*
+    RLE:
+        0x00 - 0x7F: copy next (n + 1) bytes as they are
+        0x80 - 0xFF: repeat next byte (n - 0x80 + 2) times
+*/
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut index = 0;
+
+    while index < data.len() {
+        let control = data[index];
+        index += 1;
+
+        if (control & 0x80) != 0 {
+            let count = (control & 0x7F) as usize + 2;
+            if let Some(byte) = data.get(index) {
+                output.resize(output.len() + count, *byte);
+            }
+            index += 1;
+        }
+        else {
+            let count = control as usize + 1;
+            let end = (index + count).min(data.len());
+            output.extend_from_slice(&data[index..end]);
+            index = end;
+        }
+    }
+
+    output
+}
+
+//Appends shades of every full tile row in `tiles` to `strip`.
+fn render_tiles(tiles: &[u8], palette: u8, strip: &mut Vec<u8>) {
+    for tile_row in tiles.chunks_exact(BYTES_PER_TILE_ROW) {
+        for line in 0..8 {
+            for tile in tile_row.chunks_exact(16) {
+                let low = tile[line * 2];
+                let high = tile[line * 2 + 1];
+                for bit in (0..8).rev() {
+                    let colour = (((high >> bit) & 0x01) << 1) | ((low >> bit) & 0x01);
+                    let shade = (palette >> (colour * 2)) & 0x03;
+                    strip.push(SHADES[shade as usize]);
+                }
+            }
+        }
+    }
+}
+
+//print-0001.png, print-0002.png... never overwrites earlier prints.
+fn next_free_path(output_dir: &Path) -> PathBuf {
+    let mut number = 1;
+    loop {
+        let path = output_dir.join(format!("print-{number:04}.png"));
+        if !path.exists() {
+            return path;
+        }
+        number += 1;
+    }
+}
+
+fn write_png(output_dir: &Path, strip: &[u8]) -> io::Result<PathBuf> {
+    fs::create_dir_all(output_dir)?;
+    let path = next_free_path(output_dir);
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(&path)?), WIDTH as u32, (strip.len() / WIDTH) as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(strip)?;
+
+    Ok(path)
+}
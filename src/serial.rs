@@ -0,0 +1,192 @@
+/*
+    Serial port. https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html
+
+    SB (0xFF01) - byte to send / byte received.
+    SC (0xFF02) - bit7: transfer requested/in progress, bit0: clock select (1 = internal, we are master).
+
+    With internal clock bits get shifted at 8192Hz so whole byte takes 8 * 512 = 4096 cycles.
+    With external clock other side drives the transfer, so we just wait for it.
+    Peer is asked about external clock once every bit period, not every instruction, since it can be
+    something slow like a socket.
+*/
+
+pub const SB_ADDRESS: u16 = 0xFF01;
+pub const SC_ADDRESS: u16 = 0xFF02;
+
+const CYCLES_PER_BIT: u32 = 512;
+const CYCLES_PER_TRANSFER: u32 = CYCLES_PER_BIT * 8;
+
+/*
+    Whatever is on the other end of link cable.
+*/
+pub trait LinkPeer {
+    //We are master and just clocked out `outgoing`. Returns byte peer had in its SB.
+    fn exchange(&mut self, outgoing: u8) -> u8;
+
+    //We are slave and waiting for clock. If peer clocked a transfer return its byte, it gets `outgoing` in return.
+    fn poll_external(&mut self, _outgoing: u8) -> Option<u8> {
+        None
+    }
+
+    //We are idle or master ourselves, clock pulses coming from peer should go nowhere.
+    fn ignore_external(&mut self) {}
+}
+
+/*
+    No cable attached. Data line is pulled up so master reads all 1s and slave never gets clocked.
+*/
+pub struct Disconnected;
+
+impl LinkPeer for Disconnected {
+    fn exchange(&mut self, _outgoing: u8) -> u8 {
+        0xFF
+    }
+}
+
+/*
+    Slave side of a peer whose other end clocks transfers on its own time, not from inside our exchange call.
+    poll_external just marks that we're listening and what's in our SB, `clock` is what other end calls.
+*/
+#[derive(Default)]
+pub(crate) struct SlaveState {
+    //Emulated side has SC bit 7 set with external clock.
+    listening: bool,
+    //Emulated slave's SB, sent back to master.
+    outgoing: u8,
+    //Byte master clocked in, waiting to be picked up by poll_external.
+    incoming: Option<u8>,
+}
+
+impl SlaveState {
+    pub(crate) fn poll(&mut self, outgoing: u8) -> Option<u8> {
+        if let Some(incoming) = self.incoming.take() {
+            return Some(incoming);
+        }
+
+        self.listening = true;
+        self.outgoing = outgoing;
+        None
+    }
+
+    pub(crate) fn ignore(&mut self) {
+        self.listening = false;
+        self.incoming = None;
+    }
+
+    //Master clocked `incoming` in, returns byte it gets back. Nobody listening means data line stays high.
+    pub(crate) fn clock(&mut self, incoming: u8) -> u8 {
+        if !self.listening {
+            return 0xFF;
+        }
+
+        self.listening = false;
+        self.incoming = Some(incoming);
+        self.outgoing
+    }
+}
+
+pub struct Serial {
+    SB: u8,
+    SC: u8,
+
+    transfer_cycles: u32,
+    poll_cycles: u32,
+
+    peer: Box<dyn LinkPeer>,
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Serial {
+    pub fn new() -> Serial {
+        Serial {
+            SB: 0x00,
+            SC: 0x00,
+
+            transfer_cycles: 0,
+            poll_cycles: 0,
+
+            peer: Box::new(Disconnected),
+        }
+    }
+
+    pub fn connect(&mut self, peer: Box<dyn LinkPeer>) {
+        self.peer = peer;
+    }
+
+    pub fn disconnect(&mut self) -> Box<dyn LinkPeer> {
+        std::mem::replace(&mut self.peer, Box::new(Disconnected))
+    }
+
+    pub fn read_SB(&self) -> u8 {
+        self.SB
+    }
+
+    pub fn read_SC(&self) -> u8 {
+        //Unused bits read as 1.
+        self.SC | 0x7E
+    }
+
+    pub fn write_SB(&mut self, data: u8) {
+        self.SB = data;
+    }
+
+    pub fn write_SC(&mut self, data: u8) {
+        self.SC = data & 0x81;
+        if self.transfer_requested() {
+            self.transfer_cycles = 0;
+            //Let peer know right away, not only on next bit period.
+            self.poll_cycles = CYCLES_PER_BIT;
+        }
+        if !self.transfer_requested() || self.internal_clock() {
+            self.peer.ignore_external();
+        }
+    }
+
+    fn transfer_requested(&self) -> bool {
+        ((self.SC >> 7) & 0x01) != 0
+    }
+
+    fn internal_clock(&self) -> bool {
+        (self.SC & 0x01) != 0
+    }
+
+    fn finish_transfer(&mut self, incoming: u8) {
+        self.SB = incoming;
+        self.SC &= 0x7F;
+        self.transfer_cycles = 0;
+    }
+
+    //Returns true when transfer finished and serial interrupt should be requested.
+    pub fn update(&mut self, cycles: u32) -> bool {
+        if self.transfer_requested() && self.internal_clock() {
+            self.transfer_cycles += cycles;
+            if self.transfer_cycles >= CYCLES_PER_TRANSFER {
+                let incoming = self.peer.exchange(self.SB);
+                self.finish_transfer(incoming);
+                return true;
+            }
+            return false;
+        }
+
+        self.poll_cycles += cycles;
+        if self.poll_cycles < CYCLES_PER_BIT {
+            return false;
+        }
+        self.poll_cycles = 0;
+
+        if !self.transfer_requested() {
+            self.peer.ignore_external();
+        }
+        else if let Some(incoming) = self.peer.poll_external(self.SB) {
+            self.finish_transfer(incoming);
+            return true;
+        }
+
+        false
+    }
+}
@@ -0,0 +1,259 @@
+/*
+    DIV/TIMA timer. https://gbdev.io/pandocs/Timer_Obscure_Behaviour.html
+
+    Everything runs off single 16 bit counter incremented every cycle, DIV is just its upper byte.
+    TIMA increments when selected counter bit (ANDed with enable bit) goes from 1 to 0, so writes to
+    DIV or TAC that flip that signal low also increment TIMA.
+
+    After TIMA overflows it reads 0x00 for one M-cycle, only then it's reloaded with TMA and interrupt is requested.
+    Writing TIMA in that cycle cancels reload, writes to TIMA during reload cycle are ignored and
+    writes to TMA during reload cycle go straight through to TIMA.
+*/
+
+pub const DIV_ADDRESS:  u16 = 0xFF04;
+pub const TIMA_ADDRESS: u16 = 0xFF05;
+pub const TMA_ADDRESS:  u16 = 0xFF06;
+pub const TAC_ADDRESS:  u16 = 0xFF07;
+
+const M_CYCLE: u32 = 4;
+
+pub struct Timer {
+    system_counter: u16,
+
+    TIMA: u8,
+    TMA: u8,
+    TAC: u8,
+
+    //TIMA overflowed last M-cycle, reload happens on next one.
+    overflow_pending: bool,
+    //TMA is being copied into TIMA this M-cycle.
+    reloading: bool,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer {
+            system_counter: 0,
+
+            TIMA: 0x00,
+            TMA: 0x00,
+            TAC: 0x00,
+
+            overflow_pending: false,
+            reloading: false,
+        }
+    }
+
+    //Counter bit TIMA watches for each TAC clock select. 4096Hz, 262144Hz, 65536Hz, 16384Hz.
+    fn selected_bit(&self) -> u16 {
+        match self.TAC & 0x03 {
+            0 => 9,
+            1 => 3,
+            2 => 5,
+            _ => 7,
+        }
+    }
+
+    fn signal(&self) -> bool {
+        let enabled = ((self.TAC >> 2) & 0x01) != 0;
+        enabled && ((self.system_counter >> self.selected_bit()) & 0x01) != 0
+    }
+
+    fn increment_TIMA(&mut self) {
+        let (result, overflowed) = self.TIMA.overflowing_add(1);
+        self.TIMA = result;
+        if overflowed {
+            self.overflow_pending = true;
+        }
+    }
+
+    //Returns true when timer interrupt should be requested.
+    pub fn update(&mut self, cycles: u32) -> bool {
+        let mut request_interrupt = false;
+
+        for _ in 0..(cycles / M_CYCLE) {
+            self.reloading = false;
+            if self.overflow_pending {
+                self.overflow_pending = false;
+                self.reloading = true;
+                self.TIMA = self.TMA;
+                request_interrupt = true;
+            }
+
+            let old_signal = self.signal();
+            self.system_counter = self.system_counter.wrapping_add(M_CYCLE as u16);
+            if old_signal && !self.signal() {
+                self.increment_TIMA();
+            }
+        }
+
+        request_interrupt
+    }
+
+    pub fn read_DIV(&self) -> u8 {
+        (self.system_counter >> 8) as u8
+    }
+
+    pub fn read_TIMA(&self) -> u8 {
+        self.TIMA
+    }
+
+    pub fn read_TMA(&self) -> u8 {
+        self.TMA
+    }
+
+    pub fn read_TAC(&self) -> u8 {
+        self.TAC | 0xF8
+    }
+
+    //Any write resets whole counter, not just DIV.
+    pub fn write_DIV(&mut self) {
+        let old_signal = self.signal();
+        self.system_counter = 0;
+        if old_signal {
+            self.increment_TIMA();
+        }
+    }
+
+    pub fn write_TIMA(&mut self, data: u8) {
+        if self.reloading {
+            return;
+        }
+        self.TIMA = data;
+        self.overflow_pending = false;
+    }
+
+    pub fn write_TMA(&mut self, data: u8) {
+        self.TMA = data;
+        if self.reloading {
+            self.TIMA = data;
+        }
+    }
+
+    pub fn write_TAC(&mut self, data: u8) {
+        let old_signal = self.signal();
+        self.TAC = data & 0x07;
+        if old_signal && !self.signal() {
+            self.increment_TIMA();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Enabled, 262144Hz. TIMA watches counter bit 3, so it goes up every 16 cycles.
+    const TAC_FAST: u8 = 0x05;
+
+    fn fast_timer() -> Timer {
+        let mut timer = Timer::new();
+        timer.write_TAC(TAC_FAST);
+        timer
+    }
+
+    #[test]
+    fn div_is_upper_counter_byte() {
+        let mut timer = Timer::new();
+        timer.update(255 * M_CYCLE);
+        assert_eq!(timer.read_DIV(), 0x03);
+        timer.update(M_CYCLE);
+        assert_eq!(timer.read_DIV(), 0x04);
+    }
+
+    #[test]
+    fn tima_increments_on_falling_edge() {
+        let mut timer = fast_timer();
+
+        //Bit 3 goes high at 8 cycles, TIMA only moves once it falls at 16.
+        timer.update(12);
+        assert_eq!(timer.read_TIMA(), 0);
+        timer.update(4);
+        assert_eq!(timer.read_TIMA(), 1);
+        timer.update(16 * 9);
+        assert_eq!(timer.read_TIMA(), 10);
+    }
+
+    #[test]
+    fn tma_reload_is_delayed_by_one_m_cycle() {
+        let mut timer = fast_timer();
+        timer.write_TIMA(0xFF);
+        timer.write_TMA(0x80);
+
+        assert!(!timer.update(16));
+        assert_eq!(timer.read_TIMA(), 0x00, "TIMA should read 0 for one M-cycle after overflow.");
+
+        assert!(timer.update(M_CYCLE), "Interrupt comes together with reload.");
+        assert_eq!(timer.read_TIMA(), 0x80);
+    }
+
+    #[test]
+    fn tima_write_cancels_pending_reload() {
+        let mut timer = fast_timer();
+        timer.write_TIMA(0xFF);
+        timer.write_TMA(0x80);
+        timer.update(16);
+
+        timer.write_TIMA(0x10);
+        assert!(!timer.update(M_CYCLE));
+        assert_eq!(timer.read_TIMA(), 0x10);
+    }
+
+    #[test]
+    fn writes_during_reload_cycle() {
+        let mut timer = fast_timer();
+        timer.write_TIMA(0xFF);
+        timer.write_TMA(0x80);
+        timer.update(16 + M_CYCLE);
+
+        timer.write_TIMA(0x10);
+        assert_eq!(timer.read_TIMA(), 0x80, "TIMA writes are ignored while reloading.");
+
+        timer.write_TMA(0x20);
+        assert_eq!(timer.read_TIMA(), 0x20, "TMA writes go through to TIMA while reloading.");
+
+        timer.update(M_CYCLE);
+        timer.write_TMA(0x30);
+        assert_eq!(timer.read_TIMA(), 0x20);
+    }
+
+    #[test]
+    fn div_write_with_selected_bit_high_increments_tima() {
+        let mut timer = fast_timer();
+        timer.update(8);
+
+        timer.write_DIV();
+        assert_eq!(timer.read_DIV(), 0);
+        assert_eq!(timer.read_TIMA(), 1);
+
+        //Selected bit is low now, another write does nothing.
+        timer.write_DIV();
+        assert_eq!(timer.read_TIMA(), 1);
+    }
+
+    #[test]
+    fn tac_write_that_drops_signal_increments_tima() {
+        let mut timer = fast_timer();
+        timer.update(8);
+
+        //Disabling timer while bit 3 is high.
+        timer.write_TAC(0x01);
+        assert_eq!(timer.read_TIMA(), 1);
+
+        //Switching to bit 5, which is still low.
+        timer.write_TAC(TAC_FAST);
+        timer.write_TAC(0x06);
+        assert_eq!(timer.read_TIMA(), 2);
+
+        //Enabling with selected bit low doesn't count.
+        timer.write_TAC(0x02);
+        timer.write_TAC(0x06);
+        assert_eq!(timer.read_TIMA(), 2);
+    }
+}
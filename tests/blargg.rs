@@ -0,0 +1,118 @@
+/*
+    Blargg's test roms. https://github.com/retrio/gb-test-roms
+
+    Point GAMEOOY_BLARGG_DIR at checkout of gb-test-roms to run these:
+        GAMEOOY_BLARGG_DIR=~/gb-test-roms cargo test --release --test blargg -- --ignored
+*/
+
+mod common;
+
+use std::path::Path;
+
+use gameooy::GameBoy;
+
+use common::{boot, rom_dir, SerialCapture};
+
+const ROMS_ENV_VAR: &str = "GAMEOOY_BLARGG_DIR";
+
+/*
+    Besides serial, roms also write output to cartridge RAM:
+        0xA000        - status, 0x80 while running, result code after.
+        0xA001-0xA003 - signature 0xDE 0xB0 0x61 once output is valid.
+        0xA004..      - zero terminated text.
+*/
+const STATUS_ADDRESS: u16 = 0xA000;
+const SIGNATURE_ADDRESS: u16 = 0xA001;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const TEXT_ADDRESS: u16 = 0xA004;
+const STATUS_RUNNING: u8 = 0x80;
+
+enum Outcome {
+    Passed,
+    Failed(String),
+}
+
+fn memory_output(gameboy: &GameBoy) -> Option<(u8, String)> {
+    let signature = [
+        gameboy.read_memory(SIGNATURE_ADDRESS),
+        gameboy.read_memory(SIGNATURE_ADDRESS + 1),
+        gameboy.read_memory(SIGNATURE_ADDRESS + 2),
+    ];
+    if signature != SIGNATURE {
+        return None;
+    }
+
+    let mut text = Vec::new();
+    let mut addr = TEXT_ADDRESS;
+    while addr < 0xC000 {
+        let byte = gameboy.read_memory(addr);
+        if byte == 0 {
+            break;
+        }
+        text.push(byte);
+        addr += 1;
+    }
+
+    Some((gameboy.read_memory(STATUS_ADDRESS), String::from_utf8_lossy(&text).into_owned()))
+}
+
+fn check_output(gameboy: &GameBoy, serial: &SerialCapture) -> Option<Outcome> {
+    let text = serial.text();
+    if text.contains("Passed") {
+        return Some(Outcome::Passed);
+    }
+    if text.contains("Failed") {
+        return Some(Outcome::Failed(text));
+    }
+
+    match memory_output(gameboy) {
+        Some((STATUS_RUNNING, _)) | None => None,
+        Some((0x00, _)) => Some(Outcome::Passed),
+        Some((_, text)) => Some(Outcome::Failed(text)),
+    }
+}
+
+fn run_blargg(rom: &str, frame_budget: u32) {
+    let dir = rom_dir(ROMS_ENV_VAR);
+
+    let mut gameboy = boot(&Path::new(&dir).join(rom));
+    let serial = SerialCapture::default();
+    gameboy.connect_link_peer(Box::new(serial.clone()));
+
+    for _ in 0..frame_budget {
+        gameboy.update();
+
+        match check_output(&gameboy, &serial) {
+            Some(Outcome::Passed) => return,
+            Some(Outcome::Failed(text)) => panic!("{rom} failed:\n{text}"),
+            None => {}
+        }
+    }
+
+    panic!("{rom} didn't finish within {frame_budget} frames. Output so far:\n{}", serial.text());
+}
+
+macro_rules! blargg_test {
+    ($name:ident, $rom:expr, $frame_budget:expr) => {
+        #[test]
+        #[ignore = "needs test roms in GAMEOOY_BLARGG_DIR"]
+        fn $name() {
+            run_blargg($rom, $frame_budget);
+        }
+    };
+}
+
+blargg_test!(cpu_instrs_01_special,            "cpu_instrs/individual/01-special.gb",            1_000);
+blargg_test!(cpu_instrs_02_interrupts,         "cpu_instrs/individual/02-interrupts.gb",         1_000);
+blargg_test!(cpu_instrs_03_op_sp_hl,           "cpu_instrs/individual/03-op sp,hl.gb",           1_000);
+blargg_test!(cpu_instrs_04_op_r_imm,           "cpu_instrs/individual/04-op r,imm.gb",           1_000);
+blargg_test!(cpu_instrs_05_op_rp,              "cpu_instrs/individual/05-op rp.gb",              1_000);
+blargg_test!(cpu_instrs_06_ld_r_r,             "cpu_instrs/individual/06-ld r,r.gb",             1_000);
+blargg_test!(cpu_instrs_07_jr_jp_call_ret_rst, "cpu_instrs/individual/07-jr,jp,call,ret,rst.gb", 1_000);
+blargg_test!(cpu_instrs_08_misc_instrs,        "cpu_instrs/individual/08-misc instrs.gb",        1_000);
+blargg_test!(cpu_instrs_09_op_r_r,             "cpu_instrs/individual/09-op r,r.gb",             1_500);
+blargg_test!(cpu_instrs_10_bit_ops,            "cpu_instrs/individual/10-bit ops.gb",            1_500);
+blargg_test!(cpu_instrs_11_op_a_hl,            "cpu_instrs/individual/11-op a,(hl).gb",          2_000);
+blargg_test!(cpu_instrs,                       "cpu_instrs/cpu_instrs.gb",                       4_000);
+blargg_test!(instr_timing,                     "instr_timing/instr_timing.gb",                   1_000);
+blargg_test!(halt_bug,                         "halt_bug.gb",                                    2_000);
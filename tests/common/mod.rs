@@ -0,0 +1,128 @@
+#![allow(dead_code)]
+
+/*
+    Shared helpers for test rom harnesses.
+    Test roms aren't part of this repo, each suite reads its rom directory from env var. Those tests are
+    #[ignore]d and have to be run with --ignored.
+*/
+
+use std::cell::RefCell;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use gameooy::{cartridge, GameBoy, LinkPeer};
+
+//Boot rom refuses to start without it.
+const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/*
+    Rom suites are #[ignore]d, so this only runs when asked for with --ignored. Missing rom directory
+    then fails loudly instead of passing without testing anything.
+*/
+pub fn rom_dir(env_var: &str) -> PathBuf {
+    let dir = env::var_os(env_var).map(PathBuf::from).unwrap_or_else(|| panic!("{env_var} isn't set, point it at test rom directory."));
+    assert!(dir.is_dir(), "{env_var} points at {}, which isn't a directory.", dir.display());
+    dir
+}
+
+pub fn boot(rom_path: &Path) -> GameBoy {
+    assert!(rom_path.is_file(), "Missing test rom: {}", rom_path.display());
+
+    let mut gameboy = GameBoy::new(rom_path.to_string_lossy().into_owned());
+    if let Err(error) = gameboy.load_rom() {
+        panic!("Can't load {}: {error}", rom_path.display());
+    }
+    gameboy.load_boot_rom();
+    gameboy
+}
+
+/*
+    Plain 32KiB rom with valid header and `code` at 0x0150, for tests that don't need real test roms.
+    Every interrupt vector holds RETI. Written into target tmp dir since GameBoy only loads roms from file.
+*/
+pub fn build_rom(name: &str, code: &[u8]) -> PathBuf {
+    write_rom(name, code, 0x00, 0x00)
+}
+
+//Same as build_rom(), flagged CGB only so game gets CGB only registers.
+pub fn build_cgb_rom(name: &str, code: &[u8]) -> PathBuf {
+    write_rom(name, code, 0xC0, 0x00)
+}
+
+//Same as build_rom(), flagged CGB enhanced. Still runs as DMG game.
+pub fn build_cgb_enhanced_rom(name: &str, code: &[u8]) -> PathBuf {
+    write_rom(name, code, 0x80, 0x00)
+}
+
+//Same as build_rom(), on MBC3+TIMER+BATTERY cartridge. Clock registers start out zeroed.
+pub fn build_mbc3_rom(name: &str, code: &[u8]) -> PathBuf {
+    write_rom(name, code, 0x00, 0x0F)
+}
+
+fn write_rom(name: &str, code: &[u8], cgb_flag: u8, cartridge_type: u8) -> PathBuf {
+    let mut rom = vec![0; 0x8000];
+    for vector in (0x0040..=0x0060).step_by(8) {
+        rom[vector] = 0xD9;
+    }
+    //NOP; JP 0x0150
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+    rom[0x0134..0x0134 + name.len().min(11)].copy_from_slice(&name.as_bytes()[..name.len().min(11)]);
+    rom[0x0143] = cgb_flag;
+    rom[0x0147] = cartridge_type;
+    rom[0x0150..0x0150 + code.len()].copy_from_slice(code);
+    rom[0x014D] = cartridge::header_checksum(&rom);
+
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.gb"));
+    std::fs::write(&path, &rom).unwrap();
+    path
+}
+
+/*
+    Loads rom without boot rom, cpu starts at 0x0000 and slides over NOPs into entry point right away.
+    Registers are all zero, so `code` has to set up stack itself.
+*/
+pub fn start(rom_path: &Path) -> GameBoy {
+    let mut gameboy = GameBoy::new(rom_path.to_string_lossy().into_owned());
+    if let Err(error) = gameboy.load_rom() {
+        panic!("Can't load {}: {error}", rom_path.display());
+    }
+    gameboy
+}
+
+//Steps until pc lands on `addr`, panics if it takes longer than a few frames.
+pub fn run_until_pc(gameboy: &mut GameBoy, addr: u16) {
+    for _ in 0..100_000 {
+        if gameboy.cpu().pc == addr {
+            return;
+        }
+        gameboy.step();
+    }
+    panic!("pc never reached {addr:#06x}, stuck at {:#06x}", gameboy.cpu().pc);
+}
+
+/*
+    Link peer that records every byte sent by the game, so we can read what test roms print over serial.
+*/
+#[derive(Clone, Default)]
+pub struct SerialCapture {
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SerialCapture {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.output.borrow()).into_owned()
+    }
+}
+
+impl LinkPeer for SerialCapture {
+    fn exchange(&mut self, outgoing: u8) -> u8 {
+        self.output.borrow_mut().push(outgoing);
+        0xFF
+    }
+}
@@ -0,0 +1,71 @@
+/*
+    Two GameBoys in lockstep swapping bytes over virtual link cable. Roms are built on the fly, no test roms needed.
+*/
+
+mod common;
+
+use std::path::PathBuf;
+
+use gameooy::dual::{LEFT_SIDE, RIGHT_SIDE};
+use gameooy::DualGameBoy;
+
+use common::{boot, build_rom};
+
+const FRAMES: u32 = 1000;
+
+const DONE_ADDRESS: u16 = 0xC000;
+const RECEIVED_ADDRESS: u16 = 0xC001;
+
+/*
+    Puts `sb` in SB, starts transfer with given SC and waits for it to finish.
+    Then stores received byte at 0xC001 and marks 0xC000 as done.
+*/
+fn transfer_rom(name: &str, sb: u8, sc: u8) -> PathBuf {
+    let code = [
+        0x3E, sb,           //LD A, sb
+        0xE0, 0x01,         //LDH (SB), A
+        0x3E, sc,           //LD A, sc
+        0xE0, 0x02,         //LDH (SC), A
+        0xF0, 0x02,         //wait: LDH A, (SC)
+        0xCB, 0x7F,         //BIT 7, A
+        0x20, 0xFA,         //JR NZ, wait
+        0xF0, 0x01,         //LDH A, (SB)
+        0xEA, 0x01, 0xC0,   //LD (0xC001), A
+        0x3E, 0x01,         //LD A, 1
+        0xEA, 0x00, 0xC0,   //LD (0xC000), A
+        0x18, 0xFE,         //JR -2
+    ];
+    build_rom(name, &code)
+}
+
+fn run_pair(left_name: &str, left: (u8, u8), right_name: &str, right: (u8, u8)) -> DualGameBoy {
+    let left_rom = transfer_rom(left_name, left.0, left.1);
+    let right_rom = transfer_rom(right_name, right.0, right.1);
+    let mut dual = DualGameBoy::new(boot(&left_rom), boot(&right_rom));
+
+    for _ in 0..FRAMES {
+        dual.update();
+        if [LEFT_SIDE, RIGHT_SIDE].iter().all(|side| dual.gameboy(*side).read_memory(DONE_ADDRESS) == 1) {
+            break;
+        }
+    }
+    dual
+}
+
+#[test]
+fn master_and_slave_swap_bytes() {
+    let dual = run_pair("MASTER", (0x42, 0x81), "SLAVE", (0x99, 0x80));
+
+    assert_eq!(dual.gameboy(LEFT_SIDE).read_memory(DONE_ADDRESS), 1);
+    assert_eq!(dual.gameboy(RIGHT_SIDE).read_memory(DONE_ADDRESS), 1);
+    assert_eq!(dual.gameboy(LEFT_SIDE).read_memory(RECEIVED_ADDRESS), 0x99);
+    assert_eq!(dual.gameboy(RIGHT_SIDE).read_memory(RECEIVED_ADDRESS), 0x42);
+}
+
+#[test]
+fn two_masters_read_ones() {
+    let dual = run_pair("MASTER1", (0x42, 0x81), "MASTER2", (0x99, 0x81));
+
+    assert_eq!(dual.gameboy(LEFT_SIDE).read_memory(RECEIVED_ADDRESS), 0xFF);
+    assert_eq!(dual.gameboy(RIGHT_SIDE).read_memory(RECEIVED_ADDRESS), 0xFF);
+}
@@ -0,0 +1,143 @@
+/*
+    HALT wake up and the halt bug, with roms built on the fly.
+*/
+
+mod common;
+
+use gameooy::GameBoy;
+
+use common::{build_rom, run_until_pc, start};
+
+const IF_ADDRESS: u16 = 0xFF0F;
+const RESULT_ADDRESS: u16 = 0xC000;
+
+#[test]
+fn halt_bug_reads_next_byte_twice() {
+    let code = [
+        0xF3,               //DI
+        0x06, 0x00,         //LD B, 0
+        0x3E, 0x04,         //LD A, 0x04
+        0xE0, 0xFF,         //LDH (IE), A
+        0xE0, 0x0F,         //LDH (IF), A - timer interrupt pending before HALT
+        0x76,               //HALT
+        0x04,               //INC B
+        0x78,               //LD A, B
+        0xEA, 0x00, 0xC0,   //LD (0xC000), A
+        0x18, 0xFE,         //JR -2
+    ];
+    let mut gameboy = start(&build_rom("HALTBUG", &code));
+
+    run_until_pc(&mut gameboy, 0x015F);
+    assert!(!gameboy.cpu().is_halted);
+    assert_eq!(gameboy.read_memory(RESULT_ADDRESS), 2, "INC B after HALT should run twice.");
+}
+
+#[test]
+fn halt_wakes_up_without_ime() {
+    let code = [
+        0xF3,               //DI
+        0x31, 0xFE, 0xFF,   //LD SP, 0xFFFE
+        0x3E, 0x04,         //LD A, 0x04
+        0xE0, 0xFF,         //LDH (IE), A
+        0xAF,               //XOR A
+        0xE0, 0x0F,         //LDH (IF), A
+        0x3E, 0xF0,         //LD A, 0xF0
+        0xE0, 0x05,         //LDH (TIMA), A
+        0x3E, 0x05,         //LD A, 0x05
+        0xE0, 0x07,         //LDH (TAC), A - 16 cycles per TIMA increment
+        0x76,               //HALT
+        0x3E, 0x42,         //LD A, 0x42
+        0xEA, 0x00, 0xC0,   //LD (0xC000), A
+        0x18, 0xFE,         //JR -2
+    ];
+    let mut gameboy = start(&build_rom("HALTWAKE", &code));
+
+    run_until_pc(&mut gameboy, 0x0163);
+    gameboy.step();
+    assert!(gameboy.cpu().is_halted);
+    assert_eq!(gameboy.cpu().pc, 0x0164);
+
+    //TIMA overflows after 16 * 16 cycles.
+    for _ in 0..32 {
+        gameboy.step();
+    }
+    assert!(gameboy.cpu().is_halted);
+
+    run_until_pc(&mut gameboy, 0x0169);
+    assert_eq!(gameboy.read_memory(RESULT_ADDRESS), 0x42);
+    assert_ne!(gameboy.read_memory(IF_ADDRESS) & 0x04, 0, "Interrupt shouldn't be serviced with IME off.");
+}
+
+//Timer interrupt enabled and TIMA set to overflow soon after HALT at 0x0164. `ei` goes right before HALT.
+fn timer_wake_code(ei: u8) -> [u8; 25] {
+    [
+        0xF3,               //DI
+        0x31, 0xFE, 0xFF,   //LD SP, 0xFFFE
+        0x3E, 0x04,         //LD A, 0x04
+        0xE0, 0xFF,         //LDH (IE), A
+        0xAF,               //XOR A
+        0xE0, 0x0F,         //LDH (IF), A
+        0x3E, 0xF0,         //LD A, 0xF0
+        0xE0, 0x05,         //LDH (TIMA), A
+        0x3E, 0x05,         //LD A, 0x05
+        0xE0, 0x07,         //LDH (TAC), A - 16 cycles per TIMA increment
+        ei,                 //EI or NOP
+        0x76,               //HALT
+        0x3E, 0x42,         //LD A, 0x42
+        0x18, 0xFE,         //JR -2
+    ]
+}
+
+//Steps halted cpu until it wakes up, returns cycles of the step it woke up in.
+fn step_until_awake(gameboy: &mut GameBoy) -> u32 {
+    for _ in 0..1000 {
+        let cycles = gameboy.step();
+        if !gameboy.cpu().is_halted {
+            return cycles;
+        }
+    }
+    panic!("cpu never woke up");
+}
+
+#[test]
+fn halt_exit_takes_extra_m_cycle() {
+    let mut gameboy = start(&build_rom("HALTEXIT", &timer_wake_code(0x00)));
+
+    run_until_pc(&mut gameboy, 0x0164);
+    gameboy.step();
+    assert!(gameboy.cpu().is_halted);
+
+    assert_eq!(step_until_awake(&mut gameboy), 4 + 4, "Last halted M-cycle plus one for leaving HALT.");
+    assert_eq!(gameboy.cpu().pc, 0x0165);
+}
+
+#[test]
+fn halt_exit_delays_interrupt_dispatch() {
+    let mut gameboy = start(&build_rom("HALTEXITIME", &timer_wake_code(0xFB)));
+
+    run_until_pc(&mut gameboy, 0x0164);
+    gameboy.step();
+    assert!(gameboy.cpu().is_halted);
+
+    assert_eq!(step_until_awake(&mut gameboy), 4 + 4 + 20, "Leaving HALT comes before 5 M-cycle dispatch.");
+    assert_eq!(gameboy.cpu().pc, 0x0050);
+}
+
+#[test]
+fn ei_halt_returns_after_halt() {
+    let mut gameboy = start(&build_rom("EIHALT", &timer_wake_code(0xFB)));
+
+    run_until_pc(&mut gameboy, 0x0164);
+    gameboy.step();
+    assert!(gameboy.cpu().is_halted);
+
+    run_until_pc(&mut gameboy, 0x0050);
+    assert_eq!(u16::from_le_bytes([gameboy.read_memory(0xFFFC), gameboy.read_memory(0xFFFD)]), 0x0165);
+
+    //RETI goes back to LD A, 0x42 after HALT.
+    gameboy.step();
+    assert_eq!(gameboy.cpu().pc, 0x0165);
+    assert!(gameboy.cpu().IME_enabled);
+    run_until_pc(&mut gameboy, 0x0167);
+    assert_eq!(gameboy.cpu().registers().A, 0x42);
+}
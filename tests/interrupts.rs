@@ -0,0 +1,127 @@
+/*
+    Interrupt dispatch, with roms built on the fly.
+*/
+
+mod common;
+
+use gameooy::GameBoy;
+
+use common::{build_rom, run_until_pc, start};
+
+const IF_ADDRESS: u16 = 0xFF0F;
+const IE_ADDRESS: u16 = 0xFFFF;
+
+const VBLANK: u8 = 0x01;
+const TIMER: u8 = 0x04;
+const SERIAL: u8 = 0x08;
+const JOYPAD: u8 = 0x10;
+
+/*
+    Sets up stack, IE and IF with IME off, then EI; NOP at 0x015D so dispatch happens right after NOP.
+    Returns GameBoy stopped right after dispatch and cycles of that last step.
+*/
+fn dispatch(name: &str, sp: u16, ie: u8, requested: u8) -> (GameBoy, u32) {
+    let [sp_low, sp_high] = sp.to_le_bytes();
+    let code = [
+        0xF3,                       //DI
+        0x31, sp_low, sp_high,      //LD SP, sp
+        0x3E, ie,                   //LD A, ie
+        0xE0, 0xFF,                 //LDH (IE), A
+        0x3E, requested,            //LD A, requested
+        0xE0, 0x0F,                 //LDH (IF), A
+        0xFB,                       //EI
+        0x00,                       //NOP
+        0x18, 0xFE,                 //JR -2
+    ];
+    let mut gameboy = start(&build_rom(name, &code));
+
+    run_until_pc(&mut gameboy, 0x015D);
+    assert!(!gameboy.cpu().IME_enabled, "IME should only turn on after instruction following EI.");
+
+    let cycles = gameboy.step();
+    (gameboy, cycles)
+}
+
+#[test]
+fn lowest_bit_wins_and_only_its_flag_is_cleared() {
+    let (gameboy, cycles) = dispatch("INTPRIO", 0xFFFE, SERIAL | JOYPAD, SERIAL | JOYPAD);
+
+    assert_eq!(gameboy.cpu().pc, 0x0058, "Serial should win over joypad.");
+    assert_eq!(gameboy.read_memory(IF_ADDRESS), 0xE0 | JOYPAD);
+    assert!(!gameboy.cpu().IME_enabled);
+    assert_eq!(cycles, 4 + 20, "NOP followed by 5 M-cycle dispatch.");
+}
+
+#[test]
+fn return_address_is_pushed() {
+    let (gameboy, _) = dispatch("INTPUSH", 0xFFFE, TIMER, TIMER);
+
+    assert_eq!(gameboy.cpu().pc, 0x0050);
+    assert_eq!(gameboy.cpu().sp, 0xFFFC);
+    assert_eq!(gameboy.read_memory(0xFFFD), 0x01);
+    assert_eq!(gameboy.read_memory(0xFFFC), 0x5E, "Return address should point after NOP.");
+}
+
+#[test]
+fn interrupt_not_enabled_in_ie_is_ignored() {
+    let (gameboy, cycles) = dispatch("INTMASKED", 0xFFFE, TIMER, SERIAL);
+
+    assert_eq!(gameboy.cpu().pc, 0x015E);
+    assert!(gameboy.cpu().IME_enabled);
+    assert_eq!(gameboy.read_memory(IF_ADDRESS), 0xE0 | SERIAL);
+    assert_eq!(cycles, 4);
+}
+
+#[test]
+fn push_into_ie_cancels_dispatch() {
+    //High byte of pc (0x01) lands on IE, timer is no longer enabled once vector gets picked.
+    let (gameboy, cycles) = dispatch("INTCANCEL", 0x0000, TIMER, TIMER);
+
+    assert_eq!(gameboy.cpu().pc, 0x0000);
+    assert_eq!(gameboy.read_memory(IE_ADDRESS), 0x01);
+    assert_eq!(gameboy.read_memory(IF_ADDRESS), 0xE0 | TIMER, "Cancelled interrupt stays requested.");
+    assert!(!gameboy.cpu().IME_enabled);
+    assert_eq!(cycles, 4 + 20);
+}
+
+#[test]
+fn vector_is_picked_after_push_into_ie() {
+    //Timer was the only enabled one, after push IE = 0x01 enables VBlank instead.
+    let (gameboy, _) = dispatch("INTREPICK", 0x0000, TIMER, TIMER | VBLANK);
+
+    assert_eq!(gameboy.cpu().pc, 0x0040);
+    assert_eq!(gameboy.read_memory(IF_ADDRESS), 0xE0 | TIMER);
+}
+
+#[test]
+fn ei_enables_interrupts_after_next_instruction() {
+    let (gameboy, _) = dispatch("EINOP", 0xFFFE, TIMER, TIMER);
+
+    //EI at 0x015C, handler returns after NOP.
+    assert_eq!(gameboy.cpu().pc, 0x0050);
+    assert_eq!(u16::from_le_bytes([gameboy.read_memory(0xFFFC), gameboy.read_memory(0xFFFD)]), 0x015C + 2);
+}
+
+#[test]
+fn di_right_after_ei_cancels_it() {
+    let code = [
+        0xF3,               //DI
+        0x31, 0xFE, 0xFF,   //LD SP, 0xFFFE
+        0x3E, TIMER,        //LD A, TIMER
+        0xE0, 0xFF,         //LDH (IE), A
+        0xE0, 0x0F,         //LDH (IF), A
+        0xFB,               //EI
+        0xF3,               //DI
+        0x00,               //NOP
+        0x18, 0xFD,         //JR -3
+    ];
+    let mut gameboy = start(&build_rom("EIDI", &code));
+
+    run_until_pc(&mut gameboy, 0x015A);
+    for _ in 0..1000 {
+        gameboy.step();
+        assert!((0x015B..=0x015D).contains(&gameboy.cpu().pc), "Interrupt got serviced, pc at {:#06x}", gameboy.cpu().pc);
+    }
+    assert!(!gameboy.cpu().IME_enabled);
+    assert_eq!(gameboy.read_memory(IF_ADDRESS), 0xE0 | TIMER);
+}
@@ -0,0 +1,76 @@
+/*
+    Socket link cable, both ends in one process talking over Unix domain socket.
+*/
+
+#![cfg(unix)]
+
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gameooy::link::SocketLink;
+use gameooy::LinkPeer;
+
+fn connect_pair(name: &str) -> (SocketLink, SocketLink) {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.sock"));
+    let address = format!("unix:{}", path.display());
+
+    let listen_address = address.clone();
+    let listener = thread::spawn(move || SocketLink::listen(&listen_address).unwrap());
+
+    //Listener might not be bound yet.
+    let connecting = loop {
+        if let Ok(link) = SocketLink::connect(&address) {
+            break link;
+        }
+        thread::yield_now();
+    };
+
+    (listener.join().unwrap(), connecting)
+}
+
+#[test]
+fn master_and_slave_swap_bytes() {
+    let (mut master, mut slave) = connect_pair("link-swap");
+
+    //Slave has to be listening before master clocks, like game setting SC to 0x80 first.
+    assert_eq!(slave.poll_external(0x99), None);
+
+    assert_eq!(master.exchange(0x42), 0x99);
+    assert_eq!(slave.poll_external(0x99), Some(0x42));
+}
+
+#[test]
+fn master_reads_ones_when_nobody_listens() {
+    let (mut master, mut other) = connect_pair("link-idle");
+
+    other.ignore_external();
+    assert_eq!(master.exchange(0x42), 0xFF);
+    assert_eq!(other.poll_external(0x99), None);
+}
+
+#[test]
+fn listen_keeps_existing_file() {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("link-not-a-socket.sav");
+    std::fs::write(&path, b"save").unwrap();
+
+    let error = SocketLink::listen(&format!("unix:{}", path.display())).err().expect("Listening over a file should fail.");
+    assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(std::fs::read(&path).unwrap(), b"save");
+}
+
+#[test]
+fn stalled_peer_reads_ones_without_blocking() {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("link-stalled.sock");
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    //Other end accepts but never answers.
+    let mut master = SocketLink::connect(&format!("unix:{}", path.display())).unwrap();
+    let (_stalled, _) = listener.accept().unwrap();
+
+    let start = Instant::now();
+    assert_eq!(master.exchange(0x42), 0xFF);
+    assert!(start.elapsed() < Duration::from_millis(100), "Exchange blocked for {:?}.", start.elapsed());
+}
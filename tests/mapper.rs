@@ -0,0 +1,453 @@
+/*
+    Mapper banking, checked directly against roms where every bank starts with its own number.
+*/
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use gameooy::cartridge::{self, CartridgeHeader};
+use gameooy::gameboy::CPU_FREQUENCY;
+use gameooy::mapper::{self, Mapper, Mbc1, Mbc2, Mbc3, Mbc5, Mbc7, RtcClock, ROM_BANK_SIZE, RAM_BANK_SIZE};
+use gameooy::LoadError;
+
+//Bank number at start of every bank (low byte, then high byte), so reading 0x0000 or 0x4000 tells which bank is mapped there.
+fn numbered_rom(banks: usize) -> Vec<u8> {
+    let mut rom = vec![0; banks * ROM_BANK_SIZE];
+    for bank in 0..banks {
+        rom[bank * ROM_BANK_SIZE] = bank as u8;
+        rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+    }
+    rom
+}
+
+#[test]
+fn unknown_sizes_parse_but_dont_load() {
+    let mut rom = numbered_rom(2);
+    rom[0x0148] = 0x20;
+    rom[0x0149] = 0x09;
+
+    let header = CartridgeHeader::parse(&rom).expect("Header with unknown sizes should still parse.");
+    assert_eq!(header.rom_size(), None);
+    assert_eq!(header.ram_size(), None);
+
+    let result = mapper::from_header(&header, rom.clone(), RtcClock::Emulated);
+    assert!(matches!(result, Err(LoadError::UnsupportedRomSize(0x20))));
+
+    rom[0x0148] = 0x00;
+    let header = CartridgeHeader::parse(&rom).unwrap();
+    let result = mapper::from_header(&header, rom, RtcClock::Emulated);
+    assert!(matches!(result, Err(LoadError::UnsupportedRamSize(0x09))));
+}
+
+#[test]
+fn mbc1_bank1_zero_selects_bank_one() {
+    let mut mbc1 = Mbc1::new(numbered_rom(32), 0, false);
+
+    mbc1.write_control(0x2000, 0x00);
+    assert_eq!(mbc1.read_rom(0x4000), 1);
+
+    //Only 5 bits count, 0x20 is zero as well.
+    mbc1.write_control(0x2000, 0x20);
+    assert_eq!(mbc1.read_rom(0x4000), 1);
+}
+
+#[test]
+fn mbc1_upper_bits_come_from_bank2() {
+    let mut mbc1 = Mbc1::new(numbered_rom(128), 0, false);
+
+    mbc1.write_control(0x2000, 0x03);
+    mbc1.write_control(0x4000, 0x02);
+    assert_eq!(mbc1.read_rom(0x4000), 0x43);
+    assert_eq!(mbc1.read_rom(0x0000), 0x00);
+
+    //Mode 1 maps BANK2 into 0x0000 - 0x3FFF as well.
+    mbc1.write_control(0x6000, 0x01);
+    assert_eq!(mbc1.read_rom(0x0000), 0x40);
+    assert_eq!(mbc1.read_rom(0x4000), 0x43);
+
+    //0x20, 0x40 and 0x60 can't be mapped to upper window.
+    mbc1.write_control(0x2000, 0x00);
+    mbc1.write_control(0x4000, 0x01);
+    assert_eq!(mbc1.read_rom(0x4000), 0x21);
+}
+
+#[test]
+fn mbc1_bank_wraps_to_rom_size() {
+    let mut mbc1 = Mbc1::new(numbered_rom(16), 0, false);
+
+    mbc1.write_control(0x2000, 0x12);
+    assert_eq!(mbc1.read_rom(0x4000), 0x02);
+
+    mbc1.write_control(0x4000, 0x03);
+    mbc1.write_control(0x6000, 0x01);
+    assert_eq!(mbc1.read_rom(0x0000), 0x00);
+}
+
+#[test]
+fn mbc1_ram_banking() {
+    let mut mbc1 = Mbc1::new(numbered_rom(32), 4 * RAM_BANK_SIZE, true);
+
+    assert_eq!(mbc1.read_ram(0xA000), 0xFF, "RAM should start disabled.");
+    mbc1.write_ram(0xA000, 0x12);
+    mbc1.write_control(0x0000, 0x0A);
+    assert_eq!(mbc1.read_ram(0xA000), 0x00, "Writes to disabled RAM should be ignored.");
+
+    //RAM bank only switches in mode 1.
+    mbc1.write_control(0x4000, 0x02);
+    mbc1.write_ram(0xA000, 0x22);
+    mbc1.write_control(0x6000, 0x01);
+    mbc1.write_ram(0xA000, 0x33);
+
+    assert_eq!(mbc1.read_ram(0xA000), 0x33);
+    mbc1.write_control(0x6000, 0x00);
+    assert_eq!(mbc1.read_ram(0xA000), 0x22);
+
+    let save = mbc1.save().expect("Battery backed RAM should be saved.");
+    assert_eq!(save[0], 0x22);
+    assert_eq!(save[2 * RAM_BANK_SIZE], 0x33);
+
+    mbc1.write_control(0x0000, 0x00);
+    assert_eq!(mbc1.read_ram(0xA000), 0xFF);
+}
+
+#[test]
+fn mbc1_multicart_uses_4_bit_bank1() {
+    let mut rom = numbered_rom(64);
+    let logo = cartridge::nintendo_logo();
+    let logo_start = 0x10 * ROM_BANK_SIZE + 0x0104;
+    rom[logo_start..logo_start + logo.len()].copy_from_slice(logo);
+    let mut mbc1 = Mbc1::new(rom, 0, false);
+
+    mbc1.write_control(0x2000, 0x12);
+    mbc1.write_control(0x4000, 0x01);
+    assert_eq!(mbc1.read_rom(0x4000), 0x12);
+
+    mbc1.write_control(0x4000, 0x03);
+    mbc1.write_control(0x6000, 0x01);
+    assert_eq!(mbc1.read_rom(0x0000), 0x30);
+    assert_eq!(mbc1.read_rom(0x4000), 0x32);
+}
+
+#[test]
+fn mbc2_register_select_is_address_bit_8() {
+    let mut mbc2 = Mbc2::new(numbered_rom(16), true);
+
+    //Bit 8 set selects ROMB, anywhere in 0x0000 - 0x3FFF.
+    mbc2.write_control(0x0100, 0x05);
+    assert_eq!(mbc2.read_rom(0x4000), 0x05);
+    mbc2.write_control(0x3FFF, 0x00);
+    assert_eq!(mbc2.read_rom(0x4000), 0x01);
+
+    //Bit 8 clear selects RAMG.
+    mbc2.write_control(0x3EFF, 0x0A);
+    assert_eq!(mbc2.read_rom(0x4000), 0x01);
+    assert_eq!(mbc2.read_ram(0xA000), 0xF0);
+
+    mbc2.write_control(0x4100, 0x03);
+    assert_eq!(mbc2.read_rom(0x4000), 0x01, "0x4000 - 0x7FFF isn't decoded.");
+}
+
+#[test]
+fn mbc2_ram_is_512_mirrored_nibbles() {
+    let mut mbc2 = Mbc2::new(numbered_rom(16), true);
+
+    mbc2.write_ram(0xA000, 0x0F);
+    assert_eq!(mbc2.read_ram(0xA000), 0xFF, "RAM should start disabled.");
+
+    mbc2.write_control(0x0000, 0x0A);
+    mbc2.write_ram(0xA001, 0x5A);
+    assert_eq!(mbc2.read_ram(0xA001), 0xFA);
+    assert_eq!(mbc2.read_ram(0xA201), 0xFA);
+    assert_eq!(mbc2.read_ram(0xBE01), 0xFA);
+
+    let save = mbc2.save().expect("MBC2+BATTERY should be saved.");
+    assert_eq!(save.len(), 512);
+
+    let mut restored = Mbc2::new(numbered_rom(16), true);
+    restored.restore(&save);
+    restored.write_control(0x0000, 0x0A);
+    assert_eq!(restored.read_ram(0xA001), 0xFA);
+}
+
+//Latches clock and reads S, M, H, DL, DH.
+fn read_rtc(mbc3: &mut Mbc3) -> [u8; 5] {
+    mbc3.write_control(0x6000, 0x00);
+    mbc3.write_control(0x6000, 0x01);
+
+    let mut registers = [0; 5];
+    for (register, value) in (0x08..=0x0C).zip(registers.iter_mut()) {
+        mbc3.write_control(0x4000, register);
+        *value = mbc3.read_ram(0xA000);
+    }
+    registers
+}
+
+fn write_rtc(mbc3: &mut Mbc3, register: u8, data: u8) {
+    mbc3.write_control(0x4000, register);
+    mbc3.write_ram(0xA000, data);
+}
+
+fn new_mbc3_with_rtc() -> Mbc3 {
+    let mut mbc3 = Mbc3::new(numbered_rom(128), 4 * RAM_BANK_SIZE, true, true, RtcClock::Emulated);
+    mbc3.write_control(0x0000, 0x0A);
+    mbc3
+}
+
+#[test]
+fn mbc3_rom_and_ram_banking() {
+    let mut mbc3 = new_mbc3_with_rtc();
+
+    mbc3.write_control(0x2000, 0x00);
+    assert_eq!(mbc3.read_rom(0x4000), 0x01);
+    mbc3.write_control(0x2000, 0xFF);
+    assert_eq!(mbc3.read_rom(0x4000), 0x7F);
+
+    for bank in 0..4 {
+        mbc3.write_control(0x4000, bank);
+        mbc3.write_ram(0xA000, 0x10 + bank);
+    }
+    mbc3.write_control(0x4000, 0x02);
+    assert_eq!(mbc3.read_ram(0xA000), 0x12);
+    mbc3.write_control(0x4000, 0x05);
+    assert_eq!(mbc3.read_ram(0xA000), 0xFF, "Banks between RAM and clock registers aren't mapped.");
+}
+
+#[test]
+fn mbc3_rtc_only_changes_on_latch() {
+    let mut mbc3 = new_mbc3_with_rtc();
+
+    mbc3.tick(CPU_FREQUENCY * 2);
+    assert_eq!(read_rtc(&mut mbc3)[0], 2);
+
+    mbc3.tick(CPU_FREQUENCY);
+    mbc3.write_control(0x4000, 0x08);
+    assert_eq!(mbc3.read_ram(0xA000), 2);
+
+    //Only 0x00 -> 0x01 latches.
+    mbc3.write_control(0x6000, 0x01);
+    assert_eq!(mbc3.read_ram(0xA000), 2);
+    mbc3.write_control(0x6000, 0x00);
+    mbc3.write_control(0x6000, 0x01);
+    assert_eq!(mbc3.read_ram(0xA000), 3);
+}
+
+#[test]
+fn mbc3_rtc_rolls_over_into_day_carry() {
+    let mut mbc3 = new_mbc3_with_rtc();
+
+    write_rtc(&mut mbc3, 0x08, 59);
+    write_rtc(&mut mbc3, 0x09, 59);
+    write_rtc(&mut mbc3, 0x0A, 23);
+    write_rtc(&mut mbc3, 0x0B, 0xFF);
+    write_rtc(&mut mbc3, 0x0C, 0x01);
+    assert_eq!(read_rtc(&mut mbc3), [59, 59, 23, 0xFF, 0x01]);
+
+    mbc3.tick(CPU_FREQUENCY);
+    assert_eq!(read_rtc(&mut mbc3), [0, 0, 0, 0x00, 0x80]);
+
+    //Out of range values count up to register width without carrying.
+    write_rtc(&mut mbc3, 0x08, 0xFF);
+    mbc3.tick(CPU_FREQUENCY);
+    assert_eq!(read_rtc(&mut mbc3), [0, 0, 0, 0x00, 0x80]);
+}
+
+#[test]
+fn mbc3_rtc_halt_stops_clock() {
+    let mut mbc3 = new_mbc3_with_rtc();
+
+    write_rtc(&mut mbc3, 0x0C, 0x40);
+    mbc3.tick(CPU_FREQUENCY * 5);
+    assert_eq!(read_rtc(&mut mbc3), [0, 0, 0, 0, 0x40]);
+
+    //Writing seconds restarts the second, half a second before doesn't count.
+    write_rtc(&mut mbc3, 0x0C, 0x00);
+    mbc3.tick(CPU_FREQUENCY / 2);
+    write_rtc(&mut mbc3, 0x08, 10);
+    mbc3.tick(CPU_FREQUENCY / 2);
+    assert_eq!(read_rtc(&mut mbc3)[0], 10);
+    mbc3.tick(CPU_FREQUENCY / 2);
+    assert_eq!(read_rtc(&mut mbc3)[0], 11);
+}
+
+#[test]
+fn mbc3_save_includes_rtc() {
+    let mut mbc3 = new_mbc3_with_rtc();
+    mbc3.write_ram(0xA000, 0x42);
+    write_rtc(&mut mbc3, 0x0A, 12);
+    write_rtc(&mut mbc3, 0x0B, 0x34);
+
+    let save = mbc3.save().expect("MBC3+TIMER+RAM+BATTERY should be saved.");
+    assert_eq!(save.len(), 4 * RAM_BANK_SIZE + 48);
+
+    let mut restored = Mbc3::new(numbered_rom(128), 4 * RAM_BANK_SIZE, true, true, RtcClock::Emulated);
+    restored.restore(&save);
+    restored.write_control(0x0000, 0x0A);
+    assert_eq!(restored.read_ram(0xA000), 0x42);
+    assert_eq!(read_rtc(&mut restored), [0, 0, 12, 0x34, 0x00]);
+}
+
+//Save file with clock stopped at 00:00:00 day 0, saved at given unix time.
+fn rtc_save_at(timestamp: u64) -> Vec<u8> {
+    let mut save = vec![0; 40];
+    save.extend(timestamp.to_le_bytes());
+    save
+}
+
+#[test]
+fn mbc3_host_clock_catches_up_on_restore() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let mut mbc3 = Mbc3::new(numbered_rom(128), 0, true, true, RtcClock::Host);
+    mbc3.restore(&rtc_save_at(now - (86400 + 3600 + 60 + 1)));
+    mbc3.write_control(0x0000, 0x0A);
+
+    let [seconds, minutes, hours, days_low, days_high] = read_rtc(&mut mbc3);
+    assert!((1..=2).contains(&seconds), "Seconds: {seconds}");
+    assert_eq!([minutes, hours, days_low, days_high], [1, 1, 1, 0]);
+}
+
+#[test]
+fn mbc3_restore_from_unix_epoch_sets_day_carry() {
+    //Decades worth of seconds, shouldn't take any noticeable time.
+    let start = Instant::now();
+    let mut mbc3 = Mbc3::new(numbered_rom(128), 0, true, true, RtcClock::Host);
+    mbc3.restore(&rtc_save_at(0));
+    assert!(start.elapsed() < Duration::from_millis(100), "Restore took {:?}.", start.elapsed());
+
+    mbc3.write_control(0x0000, 0x0A);
+    let registers = read_rtc(&mut mbc3);
+    assert_ne!(registers[4] & 0x80, 0, "Day counter should have overflowed.");
+    assert!(registers[0] < 60 && registers[1] < 60 && registers[2] < 24);
+}
+
+#[test]
+fn mbc5_9_bit_rom_bank() {
+    let mut mbc5 = Mbc5::new(numbered_rom(512), 0, false, false);
+
+    //Bank 0 can be mapped to upper window.
+    mbc5.write_control(0x2000, 0x00);
+    assert_eq!(mbc5.read_rom(0x4000), 0x00);
+
+    mbc5.write_control(0x2FFF, 0x23);
+    mbc5.write_control(0x3000, 0x01);
+    assert_eq!(mbc5.read_rom(0x4000), 0x23);
+    assert_eq!(mbc5.read_rom(0x4001), 0x01);
+    assert_eq!(mbc5.read_rom(0x0000), 0x00);
+}
+
+#[test]
+fn mbc5_ram_banks_and_rumble() {
+    let mut mbc5 = Mbc5::new(numbered_rom(16), 16 * RAM_BANK_SIZE, true, false);
+    mbc5.write_control(0x0000, 0x1A);
+    mbc5.write_ram(0xA000, 0x12);
+    assert_eq!(mbc5.read_ram(0xA000), 0xFF, "Only 0x0A enables RAM, upper nibble counts too.");
+
+    mbc5.write_control(0x0000, 0x0A);
+    mbc5.write_control(0x4000, 0x0F);
+    mbc5.write_ram(0xA000, 0x0F);
+    mbc5.write_control(0x4000, 0x07);
+    mbc5.write_ram(0xA000, 0x07);
+    mbc5.write_control(0x4000, 0x0F);
+    assert_eq!(mbc5.read_ram(0xA000), 0x0F);
+    assert!(!mbc5.rumble());
+
+    //On rumble cartridge bit 3 runs the motor and doesn't select RAM bank.
+    let mut rumble = Mbc5::new(numbered_rom(16), 4 * RAM_BANK_SIZE, true, true);
+    rumble.write_control(0x0000, 0x0A);
+    rumble.write_control(0x4000, 0x01);
+    rumble.write_ram(0xA000, 0x11);
+    assert!(!rumble.rumble());
+
+    rumble.write_control(0x4000, 0x09);
+    assert!(rumble.rumble());
+    assert_eq!(rumble.read_ram(0xA000), 0x11);
+
+    rumble.write_control(0x4000, 0x01);
+    assert!(!rumble.rumble());
+}
+
+fn new_mbc7() -> Mbc7 {
+    let mut mbc7 = Mbc7::new(numbered_rom(64));
+    mbc7.write_control(0x0000, 0x0A);
+    mbc7.write_control(0x4000, 0x40);
+    mbc7
+}
+
+const EEPROM_READ: u64 = 0b10;
+const EEPROM_WRITE: u64 = 0b01;
+const EEPROM_EXTENDED: u64 = 0b00;
+//Extended command picked by upper address bits.
+const EEPROM_EWEN: u64 = 0xC0;
+
+//Clocks bits into EEPROM MSB first, returning what DO had after each rising edge.
+fn eeprom_transfer(mbc7: &mut Mbc7, value: u64, bits: u32) -> u64 {
+    const CS: u8 = 0x80;
+    const CLK: u8 = 0x40;
+
+    let mut output = 0;
+    for bit in (0..bits).rev() {
+        let di = if value & (1 << bit) != 0 { 0x02 } else { 0x00 };
+        mbc7.write_ram(0xA080, CS | di);
+        mbc7.write_ram(0xA080, CS | CLK | di);
+        output = output << 1 | (mbc7.read_ram(0xA080) & 0x01) as u64;
+    }
+    output
+}
+
+//Start bit, opcode and address, then `data_bits` of data in or out. CS drops afterwards.
+fn eeprom_command(mbc7: &mut Mbc7, opcode: u64, address: u64, data: u64, data_bits: u32) -> u64 {
+    let command = (0b100 | opcode) << 8 | address;
+    let output = eeprom_transfer(mbc7, command << data_bits | data, 11 + data_bits);
+    mbc7.write_ram(0xA080, 0x00);
+    output
+}
+
+#[test]
+fn mbc7_accelerometer_latch() {
+    let mut mbc7 = new_mbc7();
+    mbc7.set_tilt(1.0, -0.5);
+
+    let read_axes = |mbc7: &Mbc7| {
+        let x = u16::from_le_bytes([mbc7.read_ram(0xA020), mbc7.read_ram(0xA030)]);
+        let y = u16::from_le_bytes([mbc7.read_ram(0xA040), mbc7.read_ram(0xA050)]);
+        (x, y)
+    };
+
+    //Latching only works after erase.
+    mbc7.write_ram(0xA010, 0xAA);
+    assert_eq!(read_axes(&mbc7), (0x8000, 0x8000));
+
+    mbc7.write_ram(0xA000, 0x55);
+    mbc7.write_ram(0xA010, 0xAA);
+    assert_eq!(read_axes(&mbc7), (0x81D0 + 0x70, 0x81D0 - 0x38));
+
+    mbc7.set_tilt(0.0, 0.0);
+    assert_eq!(read_axes(&mbc7), (0x81D0 + 0x70, 0x81D0 - 0x38), "Values stay until next latch.");
+
+    mbc7.write_control(0x4000, 0x00);
+    assert_eq!(mbc7.read_ram(0xA020), 0xFF, "Both RAMG registers have to be set.");
+}
+
+#[test]
+fn mbc7_eeprom_write_and_read() {
+    let mut mbc7 = new_mbc7();
+
+    //WRITE before EWEN is ignored.
+    eeprom_command(&mut mbc7, EEPROM_WRITE, 5, 0x1234, 16);
+    assert_eq!(eeprom_command(&mut mbc7, EEPROM_READ, 5, 0, 16) & 0xFFFF, 0xFFFF);
+
+    eeprom_command(&mut mbc7, EEPROM_EXTENDED, EEPROM_EWEN, 0, 0);
+    eeprom_command(&mut mbc7, EEPROM_WRITE, 5, 0x1234, 16);
+    eeprom_command(&mut mbc7, EEPROM_WRITE, 6, 0xABCD, 16);
+
+    //READ shifts out dummy 0 on last address bit, then data and keeps going into next word.
+    let output = eeprom_command(&mut mbc7, EEPROM_READ, 5, 0, 32);
+    assert_eq!(output >> 32 & 0x01, 0, "Dummy bit should be 0.");
+    assert_eq!(output & 0xFFFF_FFFF, 0x1234_ABCD);
+
+    let save = mbc7.save().expect("EEPROM should be saved.");
+    assert_eq!(save.len(), 256);
+    assert_eq!(&save[10..14], &[0x34, 0x12, 0xCD, 0xAB]);
+
+    let mut restored = new_mbc7();
+    restored.restore(&save);
+    assert_eq!(eeprom_command(&mut restored, EEPROM_READ, 6, 0, 16) & 0xFFFF, 0xABCD);
+}
@@ -0,0 +1,145 @@
+/*
+    Game Boy Printer, fed with packets directly like game would send them over link cable.
+*/
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use gameooy::printer::Printer;
+use gameooy::LinkPeer;
+
+const INIT: u8 = 0x01;
+const PRINT: u8 = 0x02;
+const DATA: u8 = 0x04;
+const STATUS: u8 = 0x0F;
+
+//2 tile rows, every pixel colour 3.
+const TILE_ROWS_LENGTH: usize = 640;
+
+fn output_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+//Sends whole packet, returns what printer answered to last two bytes (alive and status).
+fn send_packet(printer: &mut Printer, command: u8, compressed: bool, data: &[u8], corrupt_checksum: bool) -> (u8, u8) {
+    let mut body = vec![command, compressed as u8, data.len() as u8, (data.len() >> 8) as u8];
+    body.extend_from_slice(data);
+
+    let mut checksum = body.iter().fold(0_u16, |sum, byte| sum.wrapping_add(*byte as u16));
+    if corrupt_checksum {
+        checksum ^= 0xFFFF;
+    }
+
+    let mut packet = vec![0x88, 0x33];
+    packet.extend_from_slice(&body);
+    packet.extend_from_slice(&[checksum as u8, (checksum >> 8) as u8]);
+
+    for byte in packet {
+        assert_eq!(printer.exchange(byte), 0x00);
+    }
+    (printer.exchange(0x00), printer.exchange(0x00))
+}
+
+fn read_png(path: &PathBuf) -> (u32, u32, Vec<u8>) {
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).unwrap();
+    buffer.truncate(info.buffer_size());
+    (info.width, info.height, buffer)
+}
+
+#[test]
+fn prints_uncompressed_and_compressed_data() {
+    let dir = output_dir("printer-data");
+    let mut printer = Printer::new(&dir);
+
+    assert_eq!(send_packet(&mut printer, INIT, false, &[], false), (0x81, 0x00));
+
+    let (_, status) = send_packet(&mut printer, DATA, false, &[0xFF; TILE_ROWS_LENGTH], false);
+    assert_eq!(status & 0x08, 0x08, "Unprocessed data flag not set.");
+
+    //Same 640 bytes, 5 runs of 128.
+    let compressed = [0xFE, 0xFF].repeat(5);
+    send_packet(&mut printer, DATA, true, &compressed, false);
+    send_packet(&mut printer, DATA, false, &[], false);
+
+    //1 sheet, no feed before, 1 after, default palette.
+    let (_, status) = send_packet(&mut printer, PRINT, false, &[0x01, 0x01, 0xE4, 0x40], false);
+    assert_eq!(status & 0x02, 0x02, "Printer should be busy right after PRINT.");
+
+    let mut status = status;
+    for _ in 0..16 {
+        status = send_packet(&mut printer, STATUS, false, &[], false).1;
+    }
+    assert_eq!(status, 0x00);
+    assert_eq!(printer.pages_printed(), 1);
+
+    let (width, height, pixels) = read_png(&dir.join("print-0001.png"));
+    assert_eq!((width, height), (160, 16 + 16 + 8));
+    assert!(pixels[..160 * 32].iter().all(|pixel| *pixel == 0x00), "Image data should be black.");
+    assert!(pixels[160 * 32..].iter().all(|pixel| *pixel == 0xFF), "Feed should be white.");
+}
+
+#[test]
+fn prints_without_feed_continue_on_same_image() {
+    let dir = output_dir("printer-feed");
+    let mut printer = Printer::new(&dir);
+
+    send_packet(&mut printer, INIT, false, &[], false);
+    send_packet(&mut printer, DATA, false, &[0xFF; TILE_ROWS_LENGTH], false);
+    //Inverted palette, colour 3 is white.
+    send_packet(&mut printer, PRINT, false, &[0x01, 0x10, 0x1B, 0x40], false);
+    assert_eq!(printer.pages_printed(), 0);
+
+    send_packet(&mut printer, DATA, false, &[0xFF; TILE_ROWS_LENGTH], false);
+    send_packet(&mut printer, PRINT, false, &[0x01, 0x03, 0xE4, 0x40], false);
+    assert_eq!(printer.pages_printed(), 1);
+
+    let (_, height, pixels) = read_png(&dir.join("print-0001.png"));
+    assert_eq!(height, 8 + 16 + 16 + 3 * 8);
+    assert!(pixels[160 * 8..160 * 24].iter().all(|pixel| *pixel == 0xFF));
+    assert!(pixels[160 * 24..160 * 40].iter().all(|pixel| *pixel == 0x00));
+}
+
+#[test]
+fn bad_checksum_is_reported_and_ignored() {
+    let dir = output_dir("printer-checksum");
+    let mut printer = Printer::new(&dir);
+
+    let (alive, status) = send_packet(&mut printer, DATA, false, &[0xFF; TILE_ROWS_LENGTH], true);
+    assert_eq!(alive, 0x81);
+    assert_eq!(status, 0x01);
+
+    let (_, status) = send_packet(&mut printer, STATUS, false, &[], false);
+    assert_eq!(status, 0x00, "Data with bad checksum shouldn't be kept.");
+}
+
+#[test]
+fn print_callback_gets_saved_path_or_error() {
+    let results = Rc::new(RefCell::new(Vec::new()));
+
+    let dir = output_dir("printer-callback");
+    let mut printer = Printer::new(&dir);
+    let sink = Rc::clone(&results);
+    printer.set_print_callback(Box::new(move |result| sink.borrow_mut().push(result.map_err(|error| error.kind()))));
+    send_packet(&mut printer, DATA, false, &[0xFF; TILE_ROWS_LENGTH], false);
+    send_packet(&mut printer, PRINT, false, &[0x01, 0x01, 0xE4, 0x40], false);
+    assert_eq!(*results.borrow(), [Ok(dir.join("print-0001.png"))]);
+
+    //Output "directory" is a file, print can't be saved.
+    let file = output_dir("printer-not-a-dir");
+    fs::write(&file, []).unwrap();
+    let mut printer = Printer::new(&file);
+    let sink = Rc::clone(&results);
+    printer.set_print_callback(Box::new(move |result| sink.borrow_mut().push(result.map_err(|error| error.kind()))));
+    send_packet(&mut printer, DATA, false, &[0xFF; TILE_ROWS_LENGTH], false);
+    send_packet(&mut printer, PRINT, false, &[0x01, 0x01, 0xE4, 0x40], false);
+    assert_eq!(results.borrow().len(), 2);
+    assert!(results.borrow()[1].is_err());
+    assert_eq!(printer.pages_printed(), 0);
+}
@@ -0,0 +1,135 @@
+/*
+    MBC3 real time clock driven by cpu, along the lines of rtc3test's basic, range and sub-second tests.
+    https://github.com/aaaaaa123456789/rtc3test
+
+    Every rom writes its clock registers once, then keeps latching them and copying S, M, H, DL, DH
+    to 0xC000 - 0xC004. Holding A makes it write 10 to seconds.
+*/
+
+mod common;
+
+use gameooy::{GameBoy, A};
+
+use common::{build_mbc3_rom, start};
+
+const RESULT_ADDRESS: u16 = 0xC000;
+
+//60 frames are a bit over a second.
+const FRAMES_PER_SECOND: u32 = 60;
+
+const MAIN_LOOP: [u8; 55] = [
+    0x3E, 0x10,         //loop: LD A, 0x10
+    0xE0, 0x00,         //LDH (P1), A - buttons selected
+    0xF0, 0x00,         //LDH A, (P1)
+    0xE6, 0x01,         //AND 0x01 - A
+    0x20, 0x10,         //JR NZ, latch
+    0x3E, 0x08,         //LD A, 0x08
+    0xEA, 0x00, 0x40,   //LD (0x4000), A - S
+    0x3E, 0x0A,         //LD A, 10
+    0xEA, 0x00, 0xA0,   //LD (0xA000), A
+    0xF0, 0x00,         //wait: LDH A, (P1)
+    0xE6, 0x01,         //AND 0x01
+    0x28, 0xFA,         //JR Z, wait
+    0xAF,               //latch: XOR A
+    0xEA, 0x00, 0x60,   //LD (0x6000), A
+    0x3C,               //INC A
+    0xEA, 0x00, 0x60,   //LD (0x6000), A
+    0x21, 0x00, 0xC0,   //LD HL, 0xC000
+    0x06, 0x08,         //LD B, 0x08
+    0x78,               //read: LD A, B
+    0xEA, 0x00, 0x40,   //LD (0x4000), A
+    0xFA, 0x00, 0xA0,   //LD A, (0xA000)
+    0x22,               //LD (HL+), A
+    0x04,               //INC B
+    0x78,               //LD A, B
+    0xFE, 0x0D,         //CP 0x0D
+    0x20, 0xF2,         //JR NZ, read
+    0x18, 0xC9,         //JR loop
+];
+
+//Enables RAM and clock, writes (register, value) pairs in order and runs main loop.
+fn start_rtc_rom(name: &str, registers: &[(u8, u8)]) -> GameBoy {
+    let mut code = vec![
+        0x31, 0xFE, 0xFF,   //LD SP, 0xFFFE
+        0x3E, 0x0A,         //LD A, 0x0A
+        0xEA, 0x00, 0x00,   //LD (0x0000), A - RAMG
+    ];
+    for &(register, value) in registers {
+        code.extend([
+            0x3E, register,     //LD A, register
+            0xEA, 0x00, 0x40,   //LD (0x4000), A
+            0x3E, value,        //LD A, value
+            0xEA, 0x00, 0xA0,   //LD (0xA000), A
+        ]);
+    }
+    code.extend(MAIN_LOOP);
+
+    start(&build_mbc3_rom(name, &code))
+}
+
+fn run_frames(gameboy: &mut GameBoy, frames: u32) {
+    for _ in 0..frames {
+        gameboy.update();
+    }
+}
+
+fn read_clock(gameboy: &GameBoy) -> [u8; 5] {
+    let mut registers = [0; 5];
+    for (addr, value) in (RESULT_ADDRESS..).zip(registers.iter_mut()) {
+        *value = gameboy.read_memory(addr);
+    }
+    registers
+}
+
+#[test]
+fn rtc_ticks_and_rolls_over() {
+    let mut gameboy = start_rtc_rom("RTCBASIC", &[(0x08, 59), (0x09, 59), (0x0A, 23), (0x0B, 0xFF), (0x0C, 0x01)]);
+
+    run_frames(&mut gameboy, 2);
+    assert_eq!(read_clock(&gameboy), [59, 59, 23, 0xFF, 0x01]);
+
+    run_frames(&mut gameboy, FRAMES_PER_SECOND);
+    assert_eq!(read_clock(&gameboy), [0, 0, 0, 0x00, 0x80], "Day 511 should wrap to 0 and set carry.");
+
+    run_frames(&mut gameboy, FRAMES_PER_SECOND);
+    assert_eq!(read_clock(&gameboy), [1, 0, 0, 0x00, 0x80], "Carry stays set until written.");
+}
+
+#[test]
+fn rtc_halt_stops_clock() {
+    let mut gameboy = start_rtc_rom("RTCHALT", &[(0x08, 30), (0x0C, 0x40)]);
+
+    run_frames(&mut gameboy, FRAMES_PER_SECOND * 3);
+    assert_eq!(read_clock(&gameboy), [30, 0, 0, 0, 0x40]);
+}
+
+#[test]
+fn rtc_out_of_range_values() {
+    let mut gameboy = start_rtc_rom("RTCRANGE", &[(0x08, 0xFF), (0x09, 0xFF), (0x0A, 0xFF), (0x0B, 0x12), (0x0C, 0xBF)]);
+
+    run_frames(&mut gameboy, 2);
+    assert_eq!(read_clock(&gameboy), [0x3F, 0x3F, 0x1F, 0x12, 0x81], "Unused register bits should read 0.");
+
+    //Seconds overflow their 6 bits without ever hitting 60, minutes don't move.
+    run_frames(&mut gameboy, FRAMES_PER_SECOND);
+    assert_eq!(read_clock(&gameboy), [0x00, 0x3F, 0x1F, 0x12, 0x81]);
+}
+
+#[test]
+fn rtc_seconds_write_restarts_second() {
+    let mut gameboy = start_rtc_rom("RTCSUBSEC", &[(0x08, 0)]);
+
+    run_frames(&mut gameboy, FRAMES_PER_SECOND / 2);
+    assert_eq!(read_clock(&gameboy)[0], 0);
+
+    gameboy.key_pressed(A);
+    run_frames(&mut gameboy, 1);
+    gameboy.key_released(A);
+
+    //Without restart seconds would have ticked by now.
+    run_frames(&mut gameboy, FRAMES_PER_SECOND * 3 / 4);
+    assert_eq!(read_clock(&gameboy)[0], 10);
+
+    run_frames(&mut gameboy, FRAMES_PER_SECOND / 3);
+    assert_eq!(read_clock(&gameboy)[0], 11);
+}
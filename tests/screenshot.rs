@@ -0,0 +1,108 @@
+/*
+    Screenshot regression tests. Runs rom for fixed amount of frames and compares framebuffer
+    pixel by pixel against reference png.
+
+    Point GAMEOOY_SCREENSHOT_DIR at directory with roms and their reference images:
+        GAMEOOY_SCREENSHOT_DIR=~/screenshot-roms cargo test --release --test screenshot -- --ignored
+
+        ~/screenshot-roms/dmg-acid2/dmg-acid2.gb       https://github.com/mattcurrie/dmg-acid2
+        ~/screenshot-roms/dmg-acid2/reference-dmg.png
+
+    On mismatch diff image gets written next to other test output (target/tmp/). Matching pixels are
+    faded out, mismatching ones are red.
+*/
+
+mod common;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use gameooy::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+use common::{boot, rom_dir};
+
+const ROMS_ENV_VAR: &str = "GAMEOOY_SCREENSHOT_DIR";
+
+//Greyscale palette reference images are made with, indexed by colour id.
+const PALETTE: [[u8; 3]; 4] = [
+    [0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55],
+    [0x00, 0x00, 0x00],
+];
+
+const MISMATCH_COLOUR: [u8; 3] = [0xFF, 0x00, 0x00];
+
+const WIDTH: usize = SCREEN_WIDTH as usize;
+const HEIGHT: usize = SCREEN_HEIGHT as usize;
+
+fn load_reference(path: &Path) -> Vec<[u8; 3]> {
+    let file = File::open(path).unwrap_or_else(|error| panic!("Can't open {}: {error}", path.display()));
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().unwrap();
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).unwrap();
+
+    assert_eq!((info.width as usize, info.height as usize), (WIDTH, HEIGHT), "Reference image has wrong size: {}", path.display());
+
+    let channels = info.color_type.samples();
+    buffer[..info.buffer_size()]
+        .chunks(channels)
+        .map(|pixel| match channels {
+            1 | 2 => [pixel[0], pixel[0], pixel[0]],
+            _ => [pixel[0], pixel[1], pixel[2]],
+        })
+        .collect()
+}
+
+fn write_diff(path: &Path, actual: &[[u8; 3]], expected: &[[u8; 3]]) {
+    let mut data = Vec::with_capacity(WIDTH * HEIGHT * 3);
+    for (actual, expected) in actual.iter().zip(expected) {
+        if actual == expected {
+            data.extend(actual.iter().map(|channel| 0xC0 + channel / 4));
+        }
+        else {
+            data.extend(MISMATCH_COLOUR);
+        }
+    }
+
+    let file = BufWriter::new(File::create(path).unwrap());
+    let mut encoder = png::Encoder::new(file, SCREEN_WIDTH, SCREEN_HEIGHT);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header().unwrap().write_image_data(&data).unwrap();
+}
+
+fn run_screenshot(name: &str, rom: &str, reference: &str, frames: u32) {
+    let dir = rom_dir(ROMS_ENV_VAR);
+
+    let expected = load_reference(&dir.join(reference));
+
+    let mut gameboy = boot(&dir.join(rom));
+    for _ in 0..frames {
+        gameboy.update();
+    }
+
+    let actual: Vec<[u8; 3]> = gameboy.ppu.framebuffer().iter().map(|colour| PALETTE[*colour as usize]).collect();
+
+    let mismatches = actual.iter().zip(&expected).filter(|(actual, expected)| actual != expected).count();
+    if mismatches > 0 {
+        let diff_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}-diff.png"));
+        write_diff(&diff_path, &actual, &expected);
+        panic!("{name}: {mismatches} pixels differ from {reference}, diff written to {}", diff_path.display());
+    }
+}
+
+macro_rules! screenshot_test {
+    ($name:ident, $rom:expr, $reference:expr, $frames:expr) => {
+        #[test]
+        #[ignore = "needs test roms in GAMEOOY_SCREENSHOT_DIR"]
+        fn $name() {
+            run_screenshot(stringify!($name), $rom, $reference, $frames);
+        }
+    };
+}
+
+screenshot_test!(dmg_acid2, "dmg-acid2/dmg-acid2.gb", "dmg-acid2/reference-dmg.png", 300);
@@ -0,0 +1,98 @@
+/*
+    STOP instruction, low power mode on DMG and speed switch on CGB. Roms are built on the fly.
+*/
+
+mod common;
+
+use std::path::Path;
+
+use gameooy::{A, UP};
+
+use common::{build_cgb_enhanced_rom, build_cgb_rom, build_rom, run_until_pc, start};
+
+const DIV_ADDRESS: u16 = 0xFF04;
+const RESULT_ADDRESS: u16 = 0xC000;
+
+#[test]
+fn stop_freezes_until_selected_button() {
+    let code = [
+        0x31, 0xFE, 0xFF,   //LD SP, 0xFFFE
+        0x3E, 0x20,         //LD A, 0x20
+        0xE0, 0x00,         //LDH (P1), A - d-pad selected
+        0x06, 0x00,         //LD B, 0
+        0x05,               //wait: DEC B - lets DIV count up a bit
+        0x20, 0xFD,         //JR NZ, wait
+        0x10, 0x00,         //STOP 0x00
+        0x3E, 0x42,         //LD A, 0x42
+        0xEA, 0x00, 0xC0,   //LD (0xC000), A
+        0x18, 0xFE,         //JR -2
+    ];
+    let mut gameboy = start(&build_rom("STOP", &code));
+
+    run_until_pc(&mut gameboy, 0x015C);
+    assert_ne!(gameboy.read_memory(DIV_ADDRESS), 0);
+
+    gameboy.step();
+    assert!(gameboy.cpu().is_stopped);
+    assert_eq!(gameboy.cpu().pc, 0x015E, "STOP should take 2 bytes.");
+    assert_eq!(gameboy.read_memory(DIV_ADDRESS), 0, "STOP should reset DIV.");
+
+    for _ in 0..1000 {
+        gameboy.step();
+    }
+    assert!(gameboy.cpu().is_stopped);
+    assert_eq!(gameboy.cpu().pc, 0x015E);
+    assert_eq!(gameboy.read_memory(DIV_ADDRESS), 0, "DIV should be frozen while stopped.");
+
+    //Buttons aren't selected in P1, pressing them doesn't pull any line low.
+    gameboy.key_pressed(A);
+    gameboy.step();
+    assert!(gameboy.cpu().is_stopped);
+
+    gameboy.key_pressed(UP);
+    gameboy.step();
+    assert!(!gameboy.cpu().is_stopped);
+
+    run_until_pc(&mut gameboy, 0x0163);
+    assert_eq!(gameboy.read_memory(RESULT_ADDRESS), 0x42);
+}
+
+//Arms KEY1, runs STOP and stores KEY1 afterwards.
+const SPEED_SWITCH_CODE: [u8; 13] = [
+    0x3E, 0x01,         //LD A, 1
+    0xE0, 0x4D,         //LDH (KEY1), A
+    0x10, 0x00,         //STOP 0x00
+    0xF0, 0x4D,         //LDH A, (KEY1)
+    0xEA, 0x00, 0xC0,   //LD (0xC000), A
+    0x18, 0xFE,         //JR -2
+];
+
+#[test]
+fn stop_switches_speed_on_cgb() {
+    let mut gameboy = start(&build_cgb_rom("SPEEDSWITCH", &SPEED_SWITCH_CODE));
+
+    run_until_pc(&mut gameboy, 0x015B);
+    assert!(!gameboy.cpu().is_stopped);
+    assert_eq!(gameboy.read_memory(RESULT_ADDRESS), 0xFE, "KEY1 should report double speed and switch no longer armed.");
+}
+
+fn assert_no_speed_switch(rom_path: &Path) {
+    let mut gameboy = start(rom_path);
+
+    run_until_pc(&mut gameboy, 0x0154);
+    assert_eq!(gameboy.read_memory(0xFF4D), 0xFF);
+
+    gameboy.step();
+    assert!(gameboy.cpu().is_stopped);
+    assert_eq!(gameboy.cpu().pc, 0x0156);
+}
+
+#[test]
+fn dmg_has_no_speed_switch() {
+    assert_no_speed_switch(&build_rom("NOSWITCH", &SPEED_SWITCH_CODE));
+}
+
+#[test]
+fn cgb_enhanced_rom_runs_as_dmg() {
+    assert_no_speed_switch(&build_cgb_enhanced_rom("CGBENHANCED", &SPEED_SWITCH_CODE));
+}